@@ -1,7 +1,14 @@
 use anyhow::{bail, Context, Result};
 use clap::Parser;
 use kubert::ClientArgs;
-use linkerd_failover_cli::{check, status};
+use linkerd_failover_cli::{
+    check, describe, evacuate, failover,
+    generate::{self, SplitOptions},
+    install::{self, InstallOptions},
+    simulate, status, TrafficSplitRef,
+};
+use linkerd_failover_controller::{traffic_split::AllUnavailable, DEFAULT_SELECTOR};
+use std::{path::PathBuf, time::Duration};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -20,15 +27,88 @@ enum OutputMode {
     Json,
 }
 
+#[derive(clap::ValueEnum, Clone)]
+enum ManifestOutputMode {
+    Yaml,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone)]
+enum StatusOutputMode {
+    Table,
+    Wide,
+    Json,
+}
+
 #[derive(clap::Subcommand)]
 enum Commands {
     /// Output kubernetes manifests describing the failover extension
     Install {
+        /// Skip checking that the cluster meets the extension's requirements
         #[arg(long)]
         ignore_cluster: bool,
+
+        /// Namespace in which to install the extension
+        #[arg(long, default_value = "linkerd-failover")]
+        namespace: String,
+
+        /// Determines which TrafficSplits to consider for failover. Defaults
+        /// to failover.linkerd.io/controlled-by=linkerd-failover
+        #[arg(long)]
+        selector: Option<String>,
+
+        /// Log level of the failover controller
+        #[arg(long)]
+        log_level: Option<String>,
+
+        /// Log format of the failover controller (plain or json)
+        #[arg(long)]
+        log_format: Option<String>,
+
+        /// Docker registry of the failover controller image
+        #[arg(long)]
+        image_registry: Option<String>,
+
+        /// Name of the failover controller image
+        #[arg(long)]
+        image_name: Option<String>,
+
+        /// Tag of the failover controller image
+        #[arg(long)]
+        image_tag: Option<String>,
+
+        /// URL of a Prometheus API with Linkerd proxy metrics, enabling
+        /// failover based on success rate and latency
+        #[arg(long)]
+        prometheus_url: Option<String>,
+
+        /// Actively probe backend services annotated with
+        /// failover.linkerd.io/probe-type
+        #[arg(long)]
+        enable_probes: bool,
+
+        /// Consider Linkerd multicluster mirror services unavailable while
+        /// their remote cluster's gateway is down
+        #[arg(long)]
+        enable_multicluster: bool,
+
+        /// Region the cluster runs in, so that TrafficSplits may prefer the
+        /// ready fallbacks nearest to it
+        #[arg(long)]
+        region: Option<String>,
+
+        /// Zone the cluster runs in, within its region
+        #[arg(long, requires = "region")]
+        zone: Option<String>,
+
+        /// How TrafficSplits are weighted while their primary and all of their
+        /// fallbacks are unavailable: zero, keep or primary
+        #[arg(long)]
+        all_unavailable: Option<AllUnavailable>,
     },
 
-    /// Output kubernetes metadata manifests describing the failover extension
+    /// Output kubernetes manifests of the failover extension's resources to
+    /// delete
     Uninstall,
 
     /// Check the failover extension installation for potential problems
@@ -37,6 +117,19 @@ enum Commands {
         #[arg(long)]
         pre: bool,
 
+        /// Label selector for TrafficSplits controlled by the failover
+        /// extension
+        #[arg(
+            short = 'l',
+            long = "selector",
+            default_value = DEFAULT_SELECTOR
+        )]
+        label_selector: String,
+
+        /// Retry failing checks until they pass or this much time elapses
+        #[arg(long, value_parser = humantime::parse_duration)]
+        wait: Option<Duration>,
+
         /// Output format
         #[arg(short, long, default_value = "table")]
         output: OutputMode,
@@ -49,7 +142,189 @@ enum Commands {
         #[arg(
             short = 'l',
             long = "selector",
-            default_value = "failover.linkerd.io/controlled-by"
+            default_value = DEFAULT_SELECTOR
+        )]
+        label_selector: String,
+
+        /// Output format. The wide format also shows each backend's weight
+        /// and endpoint readiness
+        #[arg(short, long, default_value = "table")]
+        output: StatusOutputMode,
+
+        /// Keep running, re-rendering the status whenever a TrafficSplit
+        /// changes. Rows whose active backends just changed are marked with
+        /// a `*`. JSON output is emitted as one object per line
+        #[arg(short, long)]
+        watch: bool,
+    },
+
+    /// Show the failover state of a single TrafficSplit
+    Describe {
+        /// TrafficSplit to describe, as <namespace>/<name>
+        target: TrafficSplitRef,
+
+        /// Output format
+        #[arg(short, long, default_value = "table")]
+        output: OutputMode,
+    },
+
+    /// Move a TrafficSplit's traffic off of its primary service
+    Failover {
+        /// TrafficSplit to fail over, as <namespace>/<name>
+        target: TrafficSplitRef,
+
+        /// Backend service that should receive all traffic. If unset, traffic
+        /// is spread across all ready fallbacks
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Fail over even if it leaves the TrafficSplit without a ready
+        /// backend
+        #[arg(long)]
+        force: bool,
+
+        /// How long to wait for the failover controller to update the
+        /// TrafficSplit
+        #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+        timeout: Duration,
+
+        /// Output format
+        #[arg(short, long, default_value = "table")]
+        output: OutputMode,
+    },
+
+    /// Return a TrafficSplit's traffic to readiness-based routing after a
+    /// failover
+    Failback {
+        /// TrafficSplit to fail back, as <namespace>/<name>
+        target: TrafficSplitRef,
+
+        /// How long to wait for the failover controller to update the
+        /// TrafficSplit
+        #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+        timeout: Duration,
+
+        /// Output format
+        #[arg(short, long, default_value = "table")]
+        output: OutputMode,
+    },
+
+    /// Output a TrafficSplit manifest that is managed by the failover
+    /// extension
+    Generate {
+        /// TrafficSplit to generate, as <namespace>/<name>
+        target: TrafficSplitRef,
+
+        /// Service that clients send requests to. Defaults to the
+        /// TrafficSplit's name
+        #[arg(long)]
+        apex: Option<String>,
+
+        /// Backend that receives traffic while it is ready. Defaults to the
+        /// apex service
+        #[arg(long)]
+        primary: Option<String>,
+
+        /// Backend that receives traffic when the primary is not ready. May be
+        /// repeated or comma-separated
+        #[arg(long = "fallback", value_delimiter = ',')]
+        fallbacks: Vec<String>,
+
+        /// Add the services mirrored from linked clusters for the primary as
+        /// fallbacks
+        #[arg(long)]
+        mirrors: bool,
+
+        /// Value of the failover.linkerd.io/controlled-by label, which must
+        /// match the failover controller's selector
+        #[arg(long, default_value = "linkerd-failover")]
+        controlled_by: String,
+
+        /// Don't verify that the services exist. Cannot be combined with
+        /// --mirrors or --apply
+        #[arg(long, conflicts_with_all = ["mirrors", "apply"])]
+        ignore_cluster: bool,
+
+        /// Create the TrafficSplit in the cluster instead of printing it
+        #[arg(long)]
+        apply: bool,
+
+        /// With --apply, replace an existing TrafficSplit, resetting its
+        /// weights
+        #[arg(long, requires = "apply")]
+        force: bool,
+
+        /// Output format
+        #[arg(short, long, default_value = "yaml")]
+        output: ManifestOutputMode,
+    },
+
+    /// Run the failover controller's logic against TrafficSplit and
+    /// Endpoints manifests, without a cluster
+    Simulate {
+        /// File containing TrafficSplit and Endpoints manifests. May be
+        /// repeated
+        #[arg(short = 'f', long = "filename", required = true)]
+        filenames: Vec<PathBuf>,
+
+        /// File describing a sequence of changes to apply after the initial
+        /// state, as a list of steps with an `at` offset (e.g. 30s) and the
+        /// `ready`, `notReady` and `deleted` services and `manifests` that
+        /// change at that time
+        #[arg(long)]
+        timeline: Option<PathBuf>,
+
+        /// Output format
+        #[arg(short, long, default_value = "table")]
+        output: OutputMode,
+    },
+
+    /// Move traffic off of all mirror services of a remote cluster, in
+    /// every TrafficSplit controlled by the failover extension. Requires the
+    /// failover controller's multicluster support
+    Evacuate {
+        /// Name of the remote cluster, as set in its Link
+        cluster: String,
+
+        /// Namespace that Linkerd multicluster is installed into
+        #[arg(long, default_value = "linkerd-multicluster")]
+        multicluster_namespace: String,
+
+        /// Label selector for TrafficSplits controlled by the failover
+        /// extension
+        #[arg(
+            short = 'l',
+            long = "selector",
+            default_value = DEFAULT_SELECTOR
+        )]
+        label_selector: String,
+
+        /// Evacuate even if it leaves a TrafficSplit without a backend
+        /// outside of the cluster
+        #[arg(long)]
+        force: bool,
+
+        /// Output format
+        #[arg(short, long, default_value = "table")]
+        output: OutputMode,
+    },
+
+    /// Allow a remote cluster's mirror services to receive traffic again
+    /// after it was evacuated
+    Restore {
+        /// Name of the remote cluster, as set in its Link
+        cluster: String,
+
+        /// Namespace that Linkerd multicluster is installed into
+        #[arg(long, default_value = "linkerd-multicluster")]
+        multicluster_namespace: String,
+
+        /// Label selector for TrafficSplits controlled by the failover
+        /// extension
+        #[arg(
+            short = 'l',
+            long = "selector",
+            default_value = DEFAULT_SELECTOR
         )]
         label_selector: String,
 
@@ -64,7 +339,22 @@ async fn main() -> Result<()> {
     let Cli { client, command } = Cli::parse();
 
     match command {
-        Commands::Install { ignore_cluster } => {
+        Commands::Install {
+            ignore_cluster,
+            namespace,
+            selector,
+            log_level,
+            log_format,
+            image_registry,
+            image_name,
+            image_tag,
+            prometheus_url,
+            enable_probes,
+            enable_multicluster,
+            region,
+            zone,
+            all_unavailable,
+        } => {
             if !ignore_cluster {
                 let client = try_client(client).await?;
 
@@ -78,27 +368,45 @@ helm install linkerd-smi -n linkerd-smi --create-namespace linkerd-smi/linkerd-s
                 }
             }
 
-            bail!(
-                "Run the following commands to install the Failover extension:
-
-helm repo add linkerd-edge https://helm.linkerd.io/edge
-helm repo up
-
-helm install linkerd-failover -n linkerd-failover --create-namespace --devel linkerd-edge/linkerd-failover");
+            let manifests = install::render(&InstallOptions {
+                namespace,
+                selector,
+                log_level,
+                log_format,
+                image_registry,
+                image_name,
+                image_tag,
+                prometheus_url,
+                region,
+                zone,
+                all_unavailable: all_unavailable.map(|policy| policy.to_string()),
+                enable_probes,
+                enable_multicluster,
+            })?;
+            print!("{manifests}");
         }
 
         Commands::Uninstall => {
-            bail!(
-                "Run the following command to uninstall the Failover extension:
+            let client = try_client(client).await?;
 
-helm uninstall linkerd-failover"
-            );
+            let manifests = install::render_uninstall(client).await?;
+            print!("{manifests}");
         }
 
-        Commands::Check { output, pre } => {
+        Commands::Check {
+            output,
+            pre,
+            label_selector,
+            wait,
+        } => {
             let client = try_client(client).await?;
 
-            let results = check::run_checks(client, pre).await;
+            let results = match wait {
+                Some(timeout) => {
+                    check::run_checks_with_wait(client, pre, label_selector.as_str(), timeout).await
+                }
+                None => check::run_checks(client, pre, label_selector.as_str()).await,
+            };
             let success = match output {
                 OutputMode::Table => check::print_checks(results),
                 OutputMode::Json => check::json_print_checks(results),
@@ -112,15 +420,163 @@ helm uninstall linkerd-failover"
         Commands::Status {
             output,
             label_selector,
+            watch,
+        } => {
+            let client = try_client(client).await?;
+
+            if watch {
+                match output {
+                    StatusOutputMode::Table => {
+                        status::watch_print_status(client, label_selector.as_str()).await?
+                    }
+                    StatusOutputMode::Json => {
+                        status::watch_json_print_status(client, label_selector.as_str()).await?
+                    }
+                    StatusOutputMode::Wide => bail!("--watch does not support wide output"),
+                }
+                return Ok(());
+            }
+
+            let wide = matches!(output, StatusOutputMode::Wide);
+            let results = status::status(client, label_selector.as_str(), wide).await?;
+            match output {
+                StatusOutputMode::Table => status::print_status(&results),
+                StatusOutputMode::Wide => status::wide_print_status(&results),
+                StatusOutputMode::Json => status::json_print_status(&results),
+            }
+        }
+
+        Commands::Describe { target, output } => {
+            let client = try_client(client).await?;
+
+            let desc = describe::describe(client, &target).await?;
+            match output {
+                OutputMode::Table => describe::print_description(&desc),
+                OutputMode::Json => describe::json_print_description(&desc),
+            }
+        }
+
+        Commands::Failover {
+            target,
+            to,
+            force,
+            timeout,
+            output,
+        } => {
+            let client = try_client(client).await?;
+
+            let result = failover::failover(client, &target, to, force, timeout).await?;
+            let results = result.into_iter().collect::<Vec<_>>();
+            match output {
+                OutputMode::Table => status::print_status(&results),
+                OutputMode::Json => status::json_print_status(&results),
+            }
+        }
+
+        Commands::Failback {
+            target,
+            timeout,
+            output,
         } => {
             let client = try_client(client).await?;
 
-            let results = status::status(client, label_selector.as_str()).await?;
+            let result = failover::failback(client, &target, timeout).await?;
+            let results = result.into_iter().collect::<Vec<_>>();
             match output {
                 OutputMode::Table => status::print_status(&results),
                 OutputMode::Json => status::json_print_status(&results),
             }
         }
+
+        Commands::Generate {
+            target,
+            apex,
+            primary,
+            fallbacks,
+            mirrors,
+            controlled_by,
+            ignore_cluster,
+            apply,
+            force,
+            output,
+        } => {
+            let client = if ignore_cluster {
+                None
+            } else {
+                Some(try_client(client).await?)
+            };
+
+            let opts = SplitOptions {
+                target,
+                apex,
+                primary,
+                fallbacks,
+                mirrors,
+                controlled_by,
+            };
+            let split = generate::generate(client.clone(), &opts).await?;
+            if let (true, Some(client)) = (apply, client) {
+                generate::apply(client, &split, force).await?;
+                println!("trafficsplit {} applied", opts.target);
+                return Ok(());
+            }
+            match output {
+                ManifestOutputMode::Yaml => generate::print_split(&split)?,
+                ManifestOutputMode::Json => generate::json_print_split(&split),
+            }
+        }
+
+        Commands::Simulate {
+            filenames,
+            timeline,
+            output,
+        } => {
+            let sim = simulate::simulate(&filenames, timeline.as_deref())?;
+            match output {
+                OutputMode::Table => simulate::print_simulation(&sim),
+                OutputMode::Json => simulate::json_print_simulation(&sim),
+            }
+        }
+
+        Commands::Evacuate {
+            cluster,
+            multicluster_namespace,
+            label_selector,
+            force,
+            output,
+        } => {
+            let client = try_client(client).await?;
+
+            let affected = evacuate::evacuate(
+                client,
+                &cluster,
+                &multicluster_namespace,
+                &label_selector,
+                force,
+            )
+            .await?;
+            match output {
+                OutputMode::Table => evacuate::print_affected(&affected),
+                OutputMode::Json => evacuate::json_print_affected(&affected),
+            }
+        }
+
+        Commands::Restore {
+            cluster,
+            multicluster_namespace,
+            label_selector,
+            output,
+        } => {
+            let client = try_client(client).await?;
+
+            let affected =
+                evacuate::restore(client, &cluster, &multicluster_namespace, &label_selector)
+                    .await?;
+            match output {
+                OutputMode::Table => evacuate::print_affected(&affected),
+                OutputMode::Json => evacuate::json_print_affected(&affected),
+            }
+        }
     };
 
     Ok(())
@@ -1,12 +1,93 @@
+use crate::{controller, validate};
 use k8s_openapi::{
-    api::{apps::v1::Deployment, core::v1::Namespace},
+    api::{
+        apps::v1::Deployment,
+        authorization::v1::{
+            ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+            SubjectAccessReview, SubjectAccessReviewSpec,
+        },
+        core::v1::{Endpoints, Namespace, Service},
+    },
     apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
 };
-use kube::{api::ListParams, Api, Client, ResourceExt};
+use kube::{
+    api::{ListParams, PostParams},
+    Api, Client, ResourceExt,
+};
+use linkerd_failover_controller::{
+    endpoints, locality::LocalityHealth, traffic_split, TrafficSplit,
+};
 use serde::Serialize;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+};
+use tokio::time;
 
 const CHECK: &str = "√";
 const EX: &str = "×";
+const WARN: &str = "‼";
+const WAIT: &str = "/";
+
+/// The oldest Kubernetes version supported by the chart, which relies on `events.k8s.io/v1`
+const MIN_KUBERNETES_VERSION: (u64, u64, u64) = (1, 20, 0);
+const MIN_LINKERD_VERSION: (u64, u64, u64) = (2, 11, 2);
+/// Starting with this Linkerd version, the TrafficSplit CRD is provided by the linkerd-smi
+/// extension rather than by the core control plane
+const LINKERD_SMI_REQUIRED_VERSION: (u64, u64, u64) = (2, 12, 0);
+const MIN_LINKERD_SMI_VERSION: (u64, u64, u64) = (0, 2, 0);
+
+/// The chart released with the CLI, whose `appVersion` is the matching controller version
+const CHART: &str = include_str!("../../charts/linkerd-failover/Chart.yaml");
+
+const CONTROLLER_SERVICE_ACCOUNT: &str = "linkerd-failover";
+
+/// API access the failover controller's ServiceAccount requires across all namespaces
+struct Permission {
+    description: &'static str,
+    group: &'static str,
+    resource: &'static str,
+    verbs: &'static [&'static str],
+}
+
+const CONTROLLER_PERMISSIONS: &[Permission] = &[
+    Permission {
+        description: "failover controller can list and watch Endpoints",
+        group: "",
+        resource: "endpoints",
+        verbs: &["list", "watch"],
+    },
+    Permission {
+        description: "failover controller can list and watch Services",
+        group: "",
+        resource: "services",
+        verbs: &["list", "watch"],
+    },
+    Permission {
+        description: "failover controller can list and watch TrafficSplits",
+        group: "split.smi-spec.io",
+        resource: "trafficsplits",
+        verbs: &["list", "watch"],
+    },
+    Permission {
+        description: "failover controller can patch TrafficSplits",
+        group: "split.smi-spec.io",
+        resource: "trafficsplits",
+        verbs: &["patch"],
+    },
+    Permission {
+        description: "failover controller can list and watch Links",
+        group: "multicluster.linkerd.io",
+        resource: "links",
+        verbs: &["list", "watch"],
+    },
+    Permission {
+        description: "failover controller can create Events",
+        group: "events.k8s.io",
+        resource: "events",
+        verbs: &["create"],
+    },
+];
 
 #[derive(Serialize)]
 struct CheckOutput {
@@ -15,7 +96,7 @@ struct CheckOutput {
 }
 
 #[derive(Serialize)]
-struct Category {
+pub struct Category {
     category_name: &'static str,
     checks: Vec<CheckResult>,
 }
@@ -25,12 +106,13 @@ struct Category {
 enum CheckStatus {
     #[default]
     Success,
+    Warning,
     Error,
 }
 
 #[derive(Serialize, Default)]
 pub struct CheckResult {
-    description: &'static str,
+    description: Cow<'static, str>,
     result: CheckStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     hint: Option<&'static str>,
@@ -49,18 +131,18 @@ pub async fn traffic_split_check(client: Client) -> CheckResult {
     let description = "TrafficSplit CRD exists";
     match api.get_opt("trafficsplits.split.smi-spec.io").await {
         Ok(Some(_)) => CheckResult {
-            description,
+            description: description.into(),
             result: CheckStatus::Success,
             ..Default::default()
         },
         Ok(None) => CheckResult {
-            description,
+            description: description.into(),
             result: CheckStatus::Error,
             error: Some("TrafficSplit CRD is not installed".into()),
             hint: Some("https://github.com/linkerd/linkerd-smi"),
         },
         Err(err) => CheckResult {
-            description,
+            description: description.into(),
             result: CheckStatus::Error,
             error: Some(err.to_string()),
             hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
@@ -78,7 +160,7 @@ pub async fn namespace_check(client: Client) -> (CheckResult, Option<String>) {
             let ns = objs.items.first().expect("failover namespace must exist");
             (
                 CheckResult {
-                    description,
+                    description: description.into(),
                     result: CheckStatus::Success,
                     ..Default::default()
                 },
@@ -87,7 +169,7 @@ pub async fn namespace_check(client: Client) -> (CheckResult, Option<String>) {
         }
         Ok(ref objs) if objs.items.is_empty() => (
             CheckResult {
-                description,
+                description: description.into(),
                 result: CheckStatus::Error,
                 error: Some("Failover namespace not found".into()),
                 hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
@@ -96,7 +178,7 @@ pub async fn namespace_check(client: Client) -> (CheckResult, Option<String>) {
         ),
         Ok(_) => (
             CheckResult {
-                description,
+                description: description.into(),
                 result: CheckStatus::Error,
                 error: Some("Multiple failover namespaces found".into()),
                 hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
@@ -105,7 +187,7 @@ pub async fn namespace_check(client: Client) -> (CheckResult, Option<String>) {
         ),
         Err(err) => (
             CheckResult {
-                description,
+                description: description.into(),
                 result: CheckStatus::Error,
                 error: Some(err.to_string()),
                 hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
@@ -128,13 +210,13 @@ pub async fn deploy_check(client: Client, ns: &str) -> CheckResult {
 
             if has_available_replicas {
                 CheckResult {
-                    description,
+                    description: description.into(),
                     result: CheckStatus::Success,
                     ..Default::default()
                 }
             } else {
                 CheckResult {
-                    description,
+                    description: description.into(),
                     result: CheckStatus::Error,
                     error: Some("linkerd-failover deployment has no available replicas".into()),
                     hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
@@ -142,13 +224,13 @@ pub async fn deploy_check(client: Client, ns: &str) -> CheckResult {
             }
         }
         Ok(None) => CheckResult {
-            description,
+            description: description.into(),
             result: CheckStatus::Error,
             error: Some("linkerd-failover deployment not found".into()),
             hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
         },
         Err(err) => CheckResult {
-            description,
+            description: description.into(),
             result: CheckStatus::Error,
             error: Some(err.to_string()),
             hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
@@ -156,40 +238,812 @@ pub async fn deploy_check(client: Client, ns: &str) -> CheckResult {
     }
 }
 
-pub async fn run_checks(client: Client, pre: bool) -> Vec<CheckResult> {
+pub async fn kubernetes_version_check(client: Client) -> CheckResult {
+    let description = "Kubernetes version is supported";
+    let info = match client.apiserver_version().await {
+        Ok(info) => info,
+        Err(err) => {
+            return CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some(err.to_string()),
+                hint: Some("https://github.com/linkerd/linkerd-failover#requirements"),
+            }
+        }
+    };
+
+    match parse_version(&info.git_version) {
+        Some(version) if version >= MIN_KUBERNETES_VERSION => CheckResult {
+            description: description.into(),
+            ..Default::default()
+        },
+        Some(_) => CheckResult {
+            description: description.into(),
+            result: CheckStatus::Error,
+            error: Some(format!(
+                "Kubernetes {} is not supported; at least {} is required",
+                info.git_version,
+                fmt_version(MIN_KUBERNETES_VERSION)
+            )),
+            hint: Some("https://github.com/linkerd/linkerd-failover#requirements"),
+        },
+        None => CheckResult {
+            description: description.into(),
+            result: CheckStatus::Warning,
+            error: Some(format!(
+                "unable to parse Kubernetes version {}",
+                info.git_version
+            )),
+            hint: Some("https://github.com/linkerd/linkerd-failover#requirements"),
+        },
+    }
+}
+
+/// Verifies that the Linkerd control plane, and the linkerd-smi extension when the control plane
+/// no longer provides the TrafficSplit CRD, are installed in supported versions.
+pub async fn linkerd_version_checks(client: Client) -> Vec<CheckResult> {
+    let description = "Linkerd control plane version is supported";
+    let linkerd = match control_plane_version(client.clone()).await {
+        Ok(Some(version)) => version,
+        Ok(None) => {
+            return vec![CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some("Linkerd control plane not found".into()),
+                hint: Some("https://linkerd.io/2/getting-started/"),
+            }]
+        }
+        Err(err) => {
+            return vec![CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some(err.to_string()),
+                hint: Some("https://github.com/linkerd/linkerd-failover#requirements"),
+            }]
+        }
+    };
+
+    // Edge releases are versioned by date, so only stable releases can be compared against the
+    // minimum versions. Edge releases are assumed to be recent.
+    let stable = linkerd.strip_prefix("stable-").and_then(parse_version);
+    let mut results = vec![match stable {
+        Some(version) if version < MIN_LINKERD_VERSION => CheckResult {
+            description: description.into(),
+            result: CheckStatus::Error,
+            error: Some(format!(
+                "Linkerd {linkerd} is not supported; at least stable-{} is required",
+                fmt_version(MIN_LINKERD_VERSION)
+            )),
+            hint: Some("https://github.com/linkerd/linkerd-failover#requirements"),
+        },
+        _ => CheckResult {
+            description: description.into(),
+            ..Default::default()
+        },
+    }];
+
+    let description = "linkerd-smi extension version is supported";
+    let smi_required = stable.map_or(true, |v| v >= LINKERD_SMI_REQUIRED_VERSION);
+    results.push(match smi_version(client).await {
+        Ok(Some(smi)) => match parse_version(&smi) {
+            Some(version) if version >= MIN_LINKERD_SMI_VERSION => CheckResult {
+                description: description.into(),
+                ..Default::default()
+            },
+            Some(_) => CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some(format!(
+                    "linkerd-smi {smi} is not supported; at least v{} is required",
+                    fmt_version(MIN_LINKERD_SMI_VERSION)
+                )),
+                hint: Some("https://github.com/linkerd/linkerd-smi"),
+            },
+            None => CheckResult {
+                description: description.into(),
+                result: CheckStatus::Warning,
+                error: Some(format!("unable to parse linkerd-smi version {smi}")),
+                hint: Some("https://github.com/linkerd/linkerd-smi"),
+            },
+        },
+        Ok(None) if smi_required => CheckResult {
+            description: description.into(),
+            result: CheckStatus::Error,
+            error: Some(format!(
+                "linkerd-smi is required with Linkerd {linkerd} but is not installed"
+            )),
+            hint: Some("https://github.com/linkerd/linkerd-smi"),
+        },
+        Ok(None) => CheckResult {
+            description: description.into(),
+            ..Default::default()
+        },
+        Err(err) => CheckResult {
+            description: description.into(),
+            result: CheckStatus::Error,
+            error: Some(err.to_string()),
+            hint: Some("https://github.com/linkerd/linkerd-smi"),
+        },
+    });
+
+    results
+}
+
+/// Compares the controller's image tag to the CLI's version
+pub async fn controller_version_check(client: Client, ns: &str) -> CheckResult {
+    let description = "failover controller and CLI versions match";
+    // The controller is released as the chart's appVersion, independently of the CLI crate's own
+    // version.
+    let cli = chart_app_version().expect("the embedded chart must have an appVersion");
+    let api = Api::<Deployment>::namespaced(client, ns);
+    let tag = match api.get_opt("linkerd-failover").await {
+        Ok(deploy) => deploy
+            .as_ref()
+            .and_then(|d| image_tag(d, "linkerd-failover")),
+        Err(err) => {
+            return CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some(err.to_string()),
+                hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+            }
+        }
+    };
+
+    match tag {
+        Some(tag) if parse_version(&tag) == parse_version(cli) => CheckResult {
+            description: description.into(),
+            ..Default::default()
+        },
+        Some(tag) => CheckResult {
+            description: description.into(),
+            result: CheckStatus::Warning,
+            error: Some(format!(
+                "failover controller is running version {tag} but the CLI installs version {cli}"
+            )),
+            hint: Some("https://github.com/linkerd/linkerd-failover#installation"),
+        },
+        None => CheckResult {
+            description: description.into(),
+            result: CheckStatus::Warning,
+            error: Some("unable to determine the failover controller version".into()),
+            hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+        },
+    }
+}
+
+/// Reads the version of the Linkerd control plane from the `linkerd.io/created-by` annotation
+/// (e.g. `linkerd/helm stable-2.12.0`) of its destination controller
+async fn control_plane_version(client: Client) -> kube::Result<Option<String>> {
+    let namespaces = Api::<Namespace>::all(client.clone())
+        .list(&ListParams::default().labels("linkerd.io/is-control-plane=true"))
+        .await?;
+    let ns = match namespaces.items.first() {
+        Some(ns) => ns.name_any(),
+        None => return Ok(None),
+    };
+    let deploy = Api::<Deployment>::namespaced(client, &ns)
+        .get_opt("linkerd-destination")
+        .await?;
+    Ok(deploy.and_then(|d| {
+        d.annotations()
+            .get("linkerd.io/created-by")
+            .and_then(|created_by| created_by.split_whitespace().last())
+            .map(str::to_string)
+    }))
+}
+
+/// Reads the version of the linkerd-smi extension from the image tag of its adaptor
+async fn smi_version(client: Client) -> kube::Result<Option<String>> {
+    let namespaces = Api::<Namespace>::all(client.clone())
+        .list(&ListParams::default().labels("linkerd.io/extension=smi"))
+        .await?;
+    let ns = match namespaces.items.first() {
+        Some(ns) => ns.name_any(),
+        None => return Ok(None),
+    };
+    let deploy = Api::<Deployment>::namespaced(client, &ns)
+        .get_opt("smi-adaptor")
+        .await?;
+    Ok(deploy.as_ref().and_then(|d| image_tag(d, "smi-adaptor")))
+}
+
+fn image_tag(deploy: &Deployment, container: &str) -> Option<String> {
+    let image = deploy
+        .spec
+        .as_ref()?
+        .template
+        .spec
+        .as_ref()?
+        .containers
+        .iter()
+        .find(|c| c.name == container)?
+        .image
+        .as_ref()?;
+    // Ignore the digest, if any, and the registry's port by only considering the last path
+    // segment.
+    let image = image.split('@').next()?;
+    let (_, tag) = image.rsplit('/').next()?.split_once(':')?;
+    Some(tag.to_string())
+}
+
+/// Parses the `major.minor.patch` prefix of a version such as `v1.21.3-gke.100` or `0.1.3`
+/// Returns the controller version released with the CLI, i.e. the embedded chart's `appVersion`
+fn chart_app_version() -> Option<&'static str> {
+    CHART
+        .lines()
+        .find_map(|line| line.strip_prefix("appVersion:"))
+        .map(str::trim)
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let mut parts = version.splitn(3, '.');
+    let mut next = || -> Option<u64> {
+        let part = parts.next()?;
+        let digits = part
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(part, |end| &part[..end]);
+        digits.parse().ok()
+    };
+    Some((next()?, next()?, next()?))
+}
+
+fn fmt_version((major, minor, patch): (u64, u64, u64)) -> String {
+    format!("{major}.{minor}.{patch}")
+}
+
+/// Verifies that the controller's ServiceAccount is authorized to perform each of the
+/// [`CONTROLLER_PERMISSIONS`], reporting the verbs that are missing.
+pub async fn rbac_checks(client: Client, ns: &str) -> Vec<CheckResult> {
+    // SubjectAccessReviews may only be created by privileged users, so first ensure that the
+    // current user can verify the controller's permissions at all.
+    let description = "can verify failover controller permissions";
+    let review = SelfSubjectAccessReview {
+        spec: SelfSubjectAccessReviewSpec {
+            resource_attributes: Some(ResourceAttributes {
+                group: Some("authorization.k8s.io".to_string()),
+                resource: Some("subjectaccessreviews".to_string()),
+                verb: Some("create".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let allowed = Api::<SelfSubjectAccessReview>::all(client.clone())
+        .create(&PostParams::default(), &review)
+        .await
+        .map(|r| r.status.map_or(false, |s| s.allowed));
+    match allowed {
+        Ok(true) => {}
+        Ok(false) => {
+            return vec![CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some("not authorized to create SubjectAccessReviews".into()),
+                hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+            }]
+        }
+        Err(err) => {
+            return vec![CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some(err.to_string()),
+                hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+            }]
+        }
+    }
+
+    let api = Api::<SubjectAccessReview>::all(client);
+    let mut results = Vec::with_capacity(CONTROLLER_PERMISSIONS.len());
+    for permission in CONTROLLER_PERMISSIONS {
+        let mut missing = Vec::new();
+        let mut error = None;
+        for verb in permission.verbs {
+            match controller_can(&api, ns, permission, verb).await {
+                Ok(true) => {}
+                Ok(false) => missing.push(*verb),
+                Err(err) => {
+                    error = Some(err.to_string());
+                    break;
+                }
+            }
+        }
+
+        let error = error.or_else(|| {
+            if missing.is_empty() {
+                return None;
+            }
+            Some(format!(
+                "ServiceAccount {ns}/{CONTROLLER_SERVICE_ACCOUNT} cannot {} {}; check the rules \
+                 of the linkerd-failover ClusterRole and its ClusterRoleBinding",
+                missing.join(", "),
+                permission.resource,
+            ))
+        });
+        results.push(match error {
+            None => CheckResult {
+                description: permission.description.into(),
+                ..Default::default()
+            },
+            Some(error) => CheckResult {
+                description: permission.description.into(),
+                result: CheckStatus::Error,
+                error: Some(error),
+                hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+            },
+        });
+    }
+
+    results
+}
+
+async fn controller_can(
+    api: &Api<SubjectAccessReview>,
+    ns: &str,
+    permission: &Permission,
+    verb: &str,
+) -> kube::Result<bool> {
+    let review = SubjectAccessReview {
+        spec: SubjectAccessReviewSpec {
+            user: Some(format!(
+                "system:serviceaccount:{ns}:{CONTROLLER_SERVICE_ACCOUNT}"
+            )),
+            groups: Some(vec![
+                "system:serviceaccounts".to_string(),
+                format!("system:serviceaccounts:{ns}"),
+                "system:authenticated".to_string(),
+            ]),
+            resource_attributes: Some(ResourceAttributes {
+                group: Some(permission.group.to_string()),
+                resource: Some(permission.resource.to_string()),
+                verb: Some(verb.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let review = api.create(&PostParams::default(), &review).await?;
+    Ok(review.status.map_or(false, |s| s.allowed))
+}
+
+/// Validates every TrafficSplit matching the selector, reporting the splits that fail each check
+pub async fn traffic_split_resource_checks(
+    client: Client,
+    label_selector: &str,
+) -> Vec<CheckResult> {
+    let description = "can list failover TrafficSplits";
+    let api = Api::<TrafficSplit>::all(client.clone());
+    let lists = async {
+        let managed = api
+            .list(&ListParams::default().labels(label_selector))
+            .await?;
+        let all = api.list(&ListParams::default()).await?;
+        Ok::<_, kube::Error>((managed.items, all.items))
+    };
+    let (managed, all) = match lists.await {
+        Ok(lists) => lists,
+        Err(err) => {
+            return vec![CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some(err.to_string()),
+                hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+            }]
+        }
+    };
+
+    // Index the services and endpoints in each namespace that contains a managed split.
+    let namespaces = managed
+        .iter()
+        .map(|ts| ts.namespace().expect("TrafficSplits must be namespaced"))
+        .collect::<BTreeSet<_>>();
+    let mut services = HashMap::<(String, String), Service>::new();
+    let mut eps_by_service = HashMap::<(String, String), Endpoints>::new();
+    for ns in &namespaces {
+        let lists = async {
+            let svcs = Api::<Service>::namespaced(client.clone(), ns)
+                .list(&ListParams::default())
+                .await?;
+            let eps = Api::<Endpoints>::namespaced(client.clone(), ns)
+                .list(&ListParams::default())
+                .await?;
+            Ok::<_, kube::Error>((svcs, eps))
+        };
+        match lists.await {
+            Ok((svcs, eps)) => {
+                services.extend(
+                    svcs.items
+                        .into_iter()
+                        .map(|svc| ((ns.clone(), svc.name_any()), svc)),
+                );
+                eps_by_service.extend(
+                    eps.items
+                        .into_iter()
+                        .map(|ep| ((ns.clone(), ep.name_any()), ep)),
+                );
+            }
+            Err(err) => {
+                return vec![CheckResult {
+                    description: format!("can list services and endpoints in {ns}").into(),
+                    result: CheckStatus::Error,
+                    error: Some(err.to_string()),
+                    hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+                }]
+            }
+        }
+    }
+
+    // Reproduce the health sources of the controller that selects these splits, as far as the CLI
+    // can observe them.
+    let multicluster = async {
+        let controllers = controller::controllers(client.clone()).await?;
+        let controller = controllers.iter().find(|c| c.selector == label_selector);
+        let multicluster = match controller {
+            Some(controller) => controller.multicluster_health(client.clone()).await?,
+            None => None,
+        };
+        Ok::<_, anyhow::Error>((controller.cloned(), multicluster))
+    };
+    let (controller, multicluster) = match multicluster.await {
+        Ok(sources) => sources,
+        Err(err) => {
+            return vec![CheckResult {
+                description: "can read the failover controller's health sources".into(),
+                result: CheckStatus::Error,
+                error: Some(format!("{err:#}")),
+                hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+            }]
+        }
+    };
+    let default_policy = controller
+        .as_ref()
+        .map(|c| c.all_unavailable)
+        .unwrap_or_default();
+    let locality = controller
+        .as_ref()
+        .and_then(|c| c.locality.clone())
+        .map(|locality| {
+            LocalityHealth::from_objects(locality, services.values().cloned().collect())
+        });
+    let health = validate::controller_health(
+        move |ts: &TrafficSplit, svc: &str| {
+            let ns = ts.namespace().unwrap_or_default();
+            endpoints::health(eps_by_service.get(&(ns, svc.to_string())))
+        },
+        multicluster,
+        locality,
+    );
+
+    let name = |ts: &TrafficSplit| format!("{}/{}", ts.namespace().unwrap(), ts.name_any());
+    let mut results = vec![CheckResult {
+        description: description.into(),
+        ..Default::default()
+    }];
+
+    results.push(resource_check(
+        "failover TrafficSplit primary services are backends",
+        managed.iter().filter_map(|ts| {
+            let primary = validate::primary_not_backend(ts)?;
+            Some(format!(
+                "{}: primary service {primary} is not a backend",
+                name(ts)
+            ))
+        }),
+    ));
+
+    results.push(resource_check(
+        "failover TrafficSplit backend services exist",
+        managed.iter().flat_map(|ts| {
+            let ns = ts.namespace().unwrap();
+            let missing = ts
+                .spec
+                .backends
+                .iter()
+                .filter(|b| !services.contains_key(&(ns.clone(), b.service.clone())))
+                .map(|b| b.service.as_str())
+                .collect::<BTreeSet<_>>();
+            missing
+                .into_iter()
+                .map(|svc| format!("{}: service {svc} does not exist", name(ts)))
+                .collect::<Vec<_>>()
+        }),
+    ));
+
+    results.push(resource_check(
+        "failover TrafficSplits have no duplicate backends",
+        managed.iter().flat_map(|ts| {
+            validate::duplicate_backends(ts)
+                .into_iter()
+                .map(|(svc, n)| format!("{}: backend {svc} is listed {n} times", name(ts)))
+                .collect::<Vec<_>>()
+        }),
+    ));
+
+    let managed_names = managed.iter().map(name).collect::<HashSet<_>>();
+    results.push(resource_check(
+        "failover TrafficSplits do not share an apex service",
+        validate::shared_apexes(&all)
+            .into_iter()
+            .filter(|(ns, _, names)| {
+                names
+                    .iter()
+                    .any(|n| managed_names.contains(&format!("{ns}/{n}")))
+            })
+            .map(|(ns, apex, names)| {
+                format!(
+                    "{ns}: TrafficSplits {} share the apex service {apex}",
+                    names.join(", ")
+                )
+            }),
+    ));
+
+    // Without a controller selecting the splits, nothing computes their weights.
+    let controller = match controller {
+        Some(controller) => controller,
+        None if managed.is_empty() => return results,
+        None => {
+            results.push(CheckResult {
+                description: "failover TrafficSplit weights are up to date".into(),
+                result: CheckStatus::Warning,
+                error: Some(format!(
+                    "no controller selects these splits with the label selector {label_selector}"
+                )),
+                hint: Some("https://github.com/linkerd/linkerd-failover#installation"),
+            });
+            return results;
+        }
+    };
+
+    // The CLI can't verify the weights of splits that depend on health sources only the controller
+    // observes.
+    let unverifiable = managed
+        .iter()
+        .filter_map(|ts| {
+            let ns = ts.namespace().expect("TrafficSplits must be namespaced");
+            let backends = ts
+                .spec
+                .backends
+                .iter()
+                .filter_map(|b| services.get(&(ns.clone(), b.service.clone())));
+            let reason = controller.unverifiable(ts, backends)?;
+            Some((name(ts), reason))
+        })
+        .collect::<BTreeMap<_, _>>();
+    results.push(resource_check(
+        "failover TrafficSplit weights are up to date",
+        managed
+            .iter()
+            .filter(|ts| !unverifiable.contains_key(&name(ts)))
+            .filter(|ts| {
+                let members = traffic_split::group_members(ts, &managed);
+                validate::weights_outdated(ts, &members, &health, default_policy)
+            })
+            .map(|ts| {
+                format!(
+                    "{}: weights differ from those computed by the failover controller",
+                    name(ts)
+                )
+            }),
+    ));
+    if !unverifiable.is_empty() {
+        results.push(CheckResult {
+            description: "failover TrafficSplit weights can be verified".into(),
+            result: CheckStatus::Warning,
+            error: Some(
+                unverifiable
+                    .into_iter()
+                    .map(|(name, reason)| format!("{name}: {reason}"))
+                    .collect::<Vec<_>>()
+                    .join("\n    "),
+            ),
+            hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+        });
+    }
+
+    results
+}
+
+/// Flags TrafficSplits selected by more than one of the failover controllers installed in the
+/// cluster, since those controllers would fight over the split's weights.
+pub async fn competing_controllers_check(client: Client) -> CheckResult {
+    let description = "failover TrafficSplits are selected by a single controller";
+    let controllers = match controller::controllers(client.clone()).await {
+        Ok(controllers) => controllers,
+        Err(err) => {
+            return CheckResult {
+                description: description.into(),
+                result: CheckStatus::Error,
+                error: Some(err.to_string()),
+                hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+            }
+        }
+    };
+
+    let api = Api::<TrafficSplit>::all(client);
+    let mut selected_by = BTreeMap::<String, Vec<&str>>::new();
+    for controller in &controllers {
+        match api
+            .list(&ListParams::default().labels(&controller.selector))
+            .await
+        {
+            Ok(splits) => {
+                for ts in splits.items {
+                    let name = format!("{}/{}", ts.namespace().unwrap(), ts.name_any());
+                    selected_by
+                        .entry(name)
+                        .or_default()
+                        .push(&controller.namespace);
+                }
+            }
+            Err(err) => {
+                return CheckResult {
+                    description: description.into(),
+                    result: CheckStatus::Error,
+                    error: Some(err.to_string()),
+                    hint: Some("https://github.com/linkerd/linkerd-failover#troubleshooting"),
+                }
+            }
+        }
+    }
+
+    resource_check(
+        description,
+        selected_by
+            .into_iter()
+            .filter(|(_, controllers)| controllers.len() > 1)
+            .map(|(name, controllers)| {
+                format!(
+                    "{name}: selected by the failover controllers in {}",
+                    controllers.join(", ")
+                )
+            }),
+    )
+}
+
+fn resource_check(
+    description: &'static str,
+    errors: impl IntoIterator<Item = String>,
+) -> CheckResult {
+    let errors = errors.into_iter().collect::<Vec<_>>();
+    if errors.is_empty() {
+        return CheckResult {
+            description: description.into(),
+            ..Default::default()
+        };
+    }
+    CheckResult {
+        description: description.into(),
+        result: CheckStatus::Error,
+        error: Some(errors.join("\n    ")),
+        hint: Some("https://github.com/linkerd/linkerd-failover#example"),
+    }
+}
+
+pub async fn run_checks(client: Client, pre: bool, label_selector: &str) -> Vec<Category> {
     let mut results = Vec::new();
     results.push(traffic_split_check(client.clone()).await);
+
+    let mut versions = vec![kubernetes_version_check(client.clone()).await];
+    versions.extend(linkerd_version_checks(client.clone()).await);
+
     if pre {
-        return results;
+        return vec![
+            Category {
+                category_name: "linkerd-failover",
+                checks: results,
+            },
+            Category {
+                category_name: "linkerd-failover-version",
+                checks: versions,
+            },
+        ];
     }
     let (result, ns) = namespace_check(client.clone()).await;
     results.push(result);
 
     if let Some(ns) = ns {
-        results.push(deploy_check(client, &ns).await);
+        results.push(deploy_check(client.clone(), &ns).await);
+        results.extend(rbac_checks(client.clone(), &ns).await);
+        versions.push(controller_version_check(client.clone(), &ns).await);
     }
 
-    results
+    let mut resources = traffic_split_resource_checks(client.clone(), label_selector).await;
+    resources.push(competing_controllers_check(client).await);
+
+    vec![
+        Category {
+            category_name: "linkerd-failover",
+            checks: results,
+        },
+        Category {
+            category_name: "linkerd-failover-version",
+            checks: versions,
+        },
+        Category {
+            category_name: "failover resources",
+            checks: resources,
+        },
+    ]
 }
 
-pub fn print_checks(results: Vec<CheckResult>) -> bool {
-    let mut success = true;
-    let category = "linkerd-failover";
-    println!("{}", category);
-    println!("{}", category.chars().map(|_| '-').collect::<String>());
-    for result in results {
-        match result.result {
-            CheckStatus::Success => {
-                println!("{} {}", CHECK, result.description);
+/// Runs the checks until none of them fail or `timeout` elapses, backing off between attempts.
+/// Progress is written to stderr so that the final results may be printed to stdout.
+pub async fn run_checks_with_wait(
+    client: Client,
+    pre: bool,
+    label_selector: &str,
+    timeout: time::Duration,
+) -> Vec<Category> {
+    const MIN_BACKOFF: time::Duration = time::Duration::from_secs(1);
+    const MAX_BACKOFF: time::Duration = time::Duration::from_secs(10);
+
+    let deadline = time::Instant::now() + timeout;
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let categories = run_checks(client.clone(), pre, label_selector).await;
+        let failed = categories
+            .iter()
+            .flat_map(|c| &c.checks)
+            .filter(|r| matches!(r.result, CheckStatus::Error))
+            .collect::<Vec<_>>();
+        let now = time::Instant::now();
+        if failed.is_empty() || now >= deadline {
+            return categories;
+        }
+
+        let delay = backoff.min(deadline - now);
+        for result in &failed {
+            match &result.error {
+                Some(error) => eprintln!("{} {} -- {}", WAIT, result.description, error),
+                None => eprintln!("{} {}", WAIT, result.description),
             }
-            CheckStatus::Error => {
-                success = false;
-                println!("{} {}", EX, result.description);
-                if let Some(error) = result.error {
-                    println!("    {}", error);
+        }
+        eprintln!(
+            "waiting for {} check(s) to pass, retrying in {}",
+            failed.len(),
+            humantime::format_duration(time::Duration::from_secs(delay.as_secs().max(1)))
+        );
+        time::sleep(delay).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+pub fn print_checks(categories: Vec<Category>) -> bool {
+    let mut success = true;
+    for (i, category) in categories.into_iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", category.category_name);
+        println!(
+            "{}",
+            category
+                .category_name
+                .chars()
+                .map(|_| '-')
+                .collect::<String>()
+        );
+        for result in category.checks {
+            match result.result {
+                CheckStatus::Success => {
+                    println!("{} {}", CHECK, result.description);
                 }
-                if let Some(hint) = result.hint {
-                    println!("    see {} for hints", hint);
+                CheckStatus::Warning | CheckStatus::Error => {
+                    if matches!(result.result, CheckStatus::Error) {
+                        success = false;
+                        println!("{} {}", EX, result.description);
+                    } else {
+                        println!("{} {}", WARN, result.description);
+                    }
+                    if let Some(error) = result.error {
+                        println!("    {}", error);
+                    }
+                    if let Some(hint) = result.hint {
+                        println!("    see {} for hints", hint);
+                    }
                 }
             }
         }
@@ -201,16 +1055,14 @@ pub fn print_checks(results: Vec<CheckResult>) -> bool {
     success
 }
 
-pub fn json_print_checks(results: Vec<CheckResult>) -> bool {
-    let success = results
+pub fn json_print_checks(categories: Vec<Category>) -> bool {
+    let success = categories
         .iter()
-        .all(|r| matches!(r.result, CheckStatus::Success));
+        .flat_map(|c| &c.checks)
+        .all(|r| !matches!(r.result, CheckStatus::Error));
     let output = CheckOutput {
         success,
-        categories: vec![Category {
-            category_name: "linkerd-failover",
-            checks: results,
-        }],
+        categories,
     };
     serde_json::to_writer_pretty(std::io::stdout(), &output).expect("serialization failed");
     println!();
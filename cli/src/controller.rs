@@ -0,0 +1,244 @@
+//! Reads the configuration of the installed failover controllers from their Deployments, so that
+//! the CLI can reproduce their decisions.
+
+use crate::validate;
+use anyhow::{Context, Result};
+use k8s_openapi::api::{
+    apps::v1::Deployment,
+    core::v1::{Endpoints, Namespace, Service},
+};
+use kube::{api::ListParams, Api, Client, ResourceExt};
+use linkerd_failover_controller::{
+    endpoints,
+    health::AllOf,
+    locality::{Locality, LocalityHealth},
+    multicluster::{self, Link, MulticlusterHealth, CLUSTER_NAME_LABEL},
+    probe,
+    traffic_split::AllUnavailable,
+    TrafficSplit, DEFAULT_SELECTOR,
+};
+use std::collections::HashMap;
+
+/// The name of the controller's Deployment and container
+const CONTROLLER_NAME: &str = "linkerd-failover";
+
+/// The configuration of a failover controller, as set by its container's arguments
+#[derive(Clone, Debug)]
+pub(crate) struct ControllerConfig {
+    pub namespace: String,
+    pub selector: String,
+    /// Whether the controller fails over based on Prometheus metrics
+    pub metrics: bool,
+    /// Whether the controller probes annotated backend services
+    pub probes: bool,
+    pub enable_multicluster: bool,
+    pub multicluster_namespace: String,
+    /// Where the controller's cluster runs, if the controller prefers nearer fallbacks
+    pub locality: Option<Locality>,
+    /// How splits without an all-unavailable annotation are weighted while all of their backends
+    /// are unavailable
+    pub all_unavailable: AllUnavailable,
+}
+
+impl ControllerConfig {
+    /// Reads the controller's configuration from its container's arguments, falling back to the
+    /// controller's defaults
+    fn from_args(namespace: String, args: &[String]) -> Self {
+        Self {
+            namespace,
+            selector: arg(args, "--selector")
+                .or_else(|| arg(args, "-l"))
+                .unwrap_or(DEFAULT_SELECTOR)
+                .to_string(),
+            metrics: arg(args, "--prometheus-url").map_or(false, |url| !url.is_empty()),
+            probes: flag(args, "--enable-probes"),
+            enable_multicluster: flag(args, "--enable-multicluster"),
+            multicluster_namespace: arg(args, "--multicluster-namespace")
+                .unwrap_or(multicluster::DEFAULT_NAMESPACE)
+                .to_string(),
+            locality: arg(args, "--region").map(|region| Locality {
+                region: region.to_string(),
+                zone: arg(args, "--zone").map(str::to_string),
+            }),
+            all_unavailable: arg(args, "--all-unavailable")
+                .and_then(|policy| policy.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Explains why the CLI can't reproduce the controller's decision for the split, given its
+    /// backend services, if it can't. Some of the controller's health sources depend on what only
+    /// the controller observes.
+    pub(crate) fn unverifiable<'a>(
+        &self,
+        split: &TrafficSplit,
+        backends: impl IntoIterator<Item = &'a Service>,
+    ) -> Option<String> {
+        if self.metrics && validate::fails_over_on_metrics(split) {
+            return Some("it fails over on Prometheus metrics, which the CLI doesn't query".into());
+        }
+        if self.probes {
+            let probed = backends
+                .into_iter()
+                .filter(|svc| probe::is_probed(svc))
+                .map(|svc| svc.name_any())
+                .collect::<Vec<_>>();
+            if !probed.is_empty() {
+                return Some(format!(
+                    "its backends {} are probed by the controller, which the CLI doesn't do",
+                    probed.join(", ")
+                ));
+            }
+        }
+        None
+    }
+
+    /// Returns the controller's locality health source over the services in `namespace`, or `None`
+    /// if the controller's locality is not configured
+    pub(crate) async fn locality_health(
+        &self,
+        client: Client,
+        namespace: &str,
+    ) -> Result<Option<LocalityHealth>> {
+        let locality = match &self.locality {
+            Some(locality) => locality.clone(),
+            None => return Ok(None),
+        };
+        let services = Api::<Service>::namespaced(client, namespace)
+            .list(&ListParams::default())
+            .await
+            .with_context(|| format!("failed to list services in namespace {namespace}"))?;
+        Ok(Some(LocalityHealth::from_objects(locality, services.items)))
+    }
+
+    /// Returns the controller's multicluster health source over the current mirror services,
+    /// gateway mirrors and links, or `None` if the controller's multicluster support is disabled
+    pub(crate) async fn multicluster_health(
+        &self,
+        client: Client,
+    ) -> Result<Option<MulticlusterHealth>> {
+        if !self.enable_multicluster {
+            return Ok(None);
+        }
+        let namespace = &self.multicluster_namespace;
+        let services = Api::<Service>::all(client.clone())
+            .list(&ListParams::default().labels(CLUSTER_NAME_LABEL))
+            .await
+            .context("failed to list mirror services")?;
+        let gateways = Api::<Endpoints>::namespaced(client.clone(), namespace)
+            .list(&ListParams::default())
+            .await
+            .with_context(|| format!("failed to list endpoints in namespace {namespace}"))?;
+        let links = Api::<Link>::namespaced(client, namespace)
+            .list(&ListParams::default())
+            .await
+            .with_context(|| format!("failed to list links in namespace {namespace}"))?;
+        Ok(Some(MulticlusterHealth::from_objects(
+            namespace.clone(),
+            services.items,
+            gateways.items,
+            links.items,
+        )))
+    }
+}
+
+/// Lists the failover controllers installed in the cluster
+pub(crate) async fn controllers(client: Client) -> kube::Result<Vec<ControllerConfig>> {
+    let namespaces = Api::<Namespace>::all(client.clone())
+        .list(&ListParams::default().labels("linkerd.io/extension=failover"))
+        .await?;
+    let mut controllers = Vec::with_capacity(namespaces.items.len());
+    for ns in namespaces.items {
+        let ns = ns.name_any();
+        let deploy = Api::<Deployment>::namespaced(client.clone(), &ns)
+            .get_opt(CONTROLLER_NAME)
+            .await?;
+        if let Some(deploy) = deploy {
+            let args = container_args(&deploy);
+            controllers.push(ControllerConfig::from_args(ns, &args));
+        }
+    }
+    Ok(controllers)
+}
+
+/// Finds the installed failover controller that selects the split, if any
+pub(crate) async fn controller_for(
+    client: Client,
+    split: &TrafficSplit,
+) -> Result<Option<ControllerConfig>> {
+    let namespace = split.namespace().expect("TrafficSplits must be namespaced");
+    let api = Api::<TrafficSplit>::namespaced(client.clone(), &namespace);
+    let controllers = controllers(client)
+        .await
+        .context("failed to list failover controllers")?;
+    for controller in controllers {
+        let params = ListParams::default()
+            .labels(&controller.selector)
+            .fields(&format!("metadata.name={}", split.name_any()));
+        let selected = api
+            .list(&params)
+            .await
+            .context("failed to list TrafficSplits")?;
+        if !selected.items.is_empty() {
+            return Ok(Some(controller));
+        }
+    }
+    Ok(None)
+}
+
+/// Reproduces the health sources of `controller`, as far as the CLI can observe them, for a split
+/// in `namespace` whose failover group's backends have the given `Endpoints`
+pub(crate) async fn group_health(
+    client: Client,
+    controller: Option<&ControllerConfig>,
+    namespace: &str,
+    eps: HashMap<String, Endpoints>,
+) -> Result<AllOf> {
+    let (multicluster, locality) = match controller {
+        Some(controller) => (
+            controller.multicluster_health(client.clone()).await?,
+            controller.locality_health(client, namespace).await?,
+        ),
+        None => (None, None),
+    };
+    Ok(validate::controller_health(
+        move |_: &TrafficSplit, service: &str| endpoints::health(eps.get(service)),
+        multicluster,
+        locality,
+    ))
+}
+
+fn container_args(deploy: &Deployment) -> Vec<String> {
+    deploy
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.template.spec.as_ref())
+        .and_then(|spec| spec.containers.iter().find(|c| c.name == CONTROLLER_NAME))
+        .and_then(|c| c.args.clone())
+        .unwrap_or_default()
+}
+
+/// Returns the value of the argument, given as either `<name>=<value>` or `<name> <value>`
+fn arg<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix(name).and_then(|a| a.strip_prefix('=')) {
+            return Some(value);
+        }
+        if arg == name {
+            return args.next().map(String::as_str);
+        }
+    }
+    None
+}
+
+/// Returns true if the boolean flag is set
+fn flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| {
+        arg == name
+            || arg
+                .strip_prefix(name)
+                .and_then(|a| a.strip_prefix('='))
+                .map_or(false, |v| v == "true")
+    })
+}
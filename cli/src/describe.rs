@@ -0,0 +1,348 @@
+use crate::{
+    controller::{self, ControllerConfig},
+    status::group_endpoints,
+    table::{Column, Table},
+    validate, TrafficSplitRef, CONTROLLED_BY_LABEL,
+};
+use anyhow::{Context, Result};
+use k8s_openapi::api::core::v1::{Event, Service};
+use kube::{api::ListParams, Api, Client, ResourceExt};
+use linkerd_failover_controller::{
+    endpoints,
+    traffic_split::{
+        self, Decision, PrimarySource, ALL_BACKENDS_UNAVAILABLE, COMPETING_CONTROLLER, FAILOVER,
+        OVERRIDE_ANNOTATION, OVERRIDE_FALLBACKS,
+    },
+    TrafficSplit,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+const MAX_EVENTS: usize = 10;
+
+#[derive(Serialize)]
+pub struct Description {
+    namespace: String,
+    name: String,
+    apex: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    primary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    primary_source: Option<PrimarySource>,
+    #[serde(rename = "override", skip_serializing_if = "Option::is_none")]
+    override_service: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    /// The other members of the split's failover group
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    group_members: Vec<String>,
+    backends: Vec<BackendDescription>,
+    events: Vec<EventDescription>,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct BackendDescription {
+    service: String,
+    service_exists: bool,
+    /// Unset if the backend has no `Endpoints` resource
+    ready_addresses: Option<usize>,
+    not_ready_addresses: Option<usize>,
+    weight: u32,
+    desired_weight: u32,
+}
+
+#[derive(Serialize)]
+struct EventDescription {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    #[serde(rename = "type")]
+    type_: String,
+    reason: String,
+    message: String,
+}
+
+/// Gathers the failover state of a single TrafficSplit: its resolved primary, the existence and
+/// readiness of each backend, the weights the controller would assign, recent failover events,
+/// and any misconfiguration.
+pub async fn describe(client: Client, target: &TrafficSplitRef) -> Result<Description> {
+    let api = Api::<TrafficSplit>::namespaced(client.clone(), &target.namespace);
+    let split = api
+        .get_opt(&target.name)
+        .await
+        .with_context(|| format!("failed to get trafficsplit {target}"))?
+        .with_context(|| format!("trafficsplit {target} not found"))?;
+
+    let services = Api::<Service>::namespaced(client.clone(), &target.namespace);
+    let mut existing = HashMap::new();
+    for backend in &split.spec.backends {
+        let svc = services
+            .get_opt(&backend.service)
+            .await
+            .with_context(|| format!("failed to get service {}", backend.service))?;
+        if let Some(svc) = svc {
+            existing.insert(backend.service.clone(), svc);
+        }
+    }
+    let controller = controller::controller_for(client.clone(), &split).await?;
+    let selector = controller
+        .as_ref()
+        .map_or(CONTROLLED_BY_LABEL, |c| c.selector.as_str());
+    let (members, eps) = group_endpoints(client.clone(), &split, selector).await?;
+    let events = failover_events(client.clone(), target).await?;
+
+    let primary = traffic_split::primary_service(&split);
+    let unverifiable = controller
+        .as_ref()
+        .and_then(|c| c.unverifiable(&split, existing.values()));
+    let health =
+        controller::group_health(client, controller.as_ref(), &target.namespace, eps.clone())
+            .await?;
+    let decision =
+        traffic_split::decide_group(&split, &members.iter().collect::<Vec<_>>(), &health);
+    // Splits without an all-unavailable annotation follow the policy the controller is configured
+    // with.
+    let default_policy = controller
+        .as_ref()
+        .map(|c| c.all_unavailable)
+        .unwrap_or_default();
+    let desired = decision
+        .clone()
+        .map(|d| {
+            let policy = traffic_split::all_unavailable_policy(&split, default_policy);
+            traffic_split::apply_all_unavailable(&split, d, policy).backends
+        })
+        .unwrap_or_default();
+    let backends = split
+        .spec
+        .backends
+        .iter()
+        .zip(&desired)
+        .map(|(current, desired)| {
+            let ep = eps.get(&current.service);
+            BackendDescription {
+                service: current.service.clone(),
+                service_exists: existing.contains_key(&current.service),
+                ready_addresses: ep.map(endpoints::ready_addresses),
+                not_ready_addresses: ep.map(endpoints::not_ready_addresses),
+                weight: current.weight,
+                desired_weight: desired.weight,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Description {
+        namespace: target.namespace.clone(),
+        name: target.name.clone(),
+        apex: split.spec.service.clone(),
+        primary: primary.map(|p| p.service.to_string()),
+        primary_source: primary.map(|p| p.source),
+        override_service: split.annotations().get(OVERRIDE_ANNOTATION).cloned(),
+        group: traffic_split::group(&split).map(str::to_string),
+        group_members: members.iter().skip(1).map(|m| m.name_any()).collect(),
+        warnings: warnings(
+            &split,
+            &backends,
+            decision.as_ref(),
+            controller.as_ref(),
+            unverifiable.as_deref(),
+        ),
+        backends,
+        events,
+    })
+}
+
+/// Lists the most recent events the failover controller recorded for the split, newest first
+async fn failover_events(
+    client: Client,
+    target: &TrafficSplitRef,
+) -> Result<Vec<EventDescription>> {
+    let api = Api::<Event>::namespaced(client, &target.namespace);
+    // Field selectors can't match one of several reasons, so the reasons are filtered here.
+    let params = ListParams::default().fields(&format!(
+        "involvedObject.kind=TrafficSplit,involvedObject.name={}",
+        target.name
+    ));
+    let mut events = api
+        .list(&params)
+        .await
+        .context("failed to list events")?
+        .items;
+    events.retain(|ev| {
+        matches!(
+            ev.reason.as_deref(),
+            Some(FAILOVER | ALL_BACKENDS_UNAVAILABLE | COMPETING_CONTROLLER)
+        )
+    });
+
+    let time = |ev: &Event| {
+        ev.series
+            .as_ref()
+            .and_then(|s| s.last_observed_time.as_ref())
+            .or(ev.event_time.as_ref())
+            .map(|t| t.0)
+            .or_else(|| ev.last_timestamp.as_ref().map(|t| t.0))
+            .or_else(|| ev.first_timestamp.as_ref().map(|t| t.0))
+    };
+    events.sort_by_key(|ev| std::cmp::Reverse(time(ev)));
+    Ok(events
+        .iter()
+        .take(MAX_EVENTS)
+        .map(|ev| EventDescription {
+            time: time(ev).map(|t| t.to_rfc3339()),
+            type_: ev.type_.clone().unwrap_or_default(),
+            reason: ev.reason.clone().unwrap_or_default(),
+            message: ev.message.clone().unwrap_or_default(),
+        })
+        .collect())
+}
+
+fn warnings(
+    split: &TrafficSplit,
+    backends: &[BackendDescription],
+    decision: Option<&Decision>,
+    controller: Option<&ControllerConfig>,
+    unverifiable: Option<&str>,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    // Splits that match an installed controller's selector are managed, whatever its selector is.
+    if controller.is_none() {
+        warnings.push(format!(
+            "no failover controller selects the split; the default controller selects splits with the {CONTROLLED_BY_LABEL} label"
+        ));
+    }
+
+    if split.spec.backends.is_empty() {
+        warnings.push("the split has no backends".to_string());
+    }
+    if let Some(primary) = validate::primary_not_backend(split) {
+        warnings.push(format!(
+            "the primary service {primary} is not a backend of the split"
+        ));
+    }
+
+    if let Some(service) = split.annotations().get(OVERRIDE_ANNOTATION) {
+        if service != OVERRIDE_FALLBACKS && !backends.iter().any(|b| b.service == *service) {
+            warnings.push(format!(
+                "the override names {service}, which is not a backend of the split, so it is ignored"
+            ));
+        }
+    }
+
+    for (service, n) in validate::duplicate_backends(split) {
+        warnings.push(format!("backend {service} is listed {n} times"));
+    }
+    for backend in backends {
+        if !backend.service_exists {
+            warnings.push(format!("service {} does not exist", backend.service));
+        } else if backend.ready_addresses.is_none() {
+            warnings.push(format!("service {} has no endpoints", backend.service));
+        }
+    }
+
+    if let Some(decision) = decision {
+        if decision.all_unavailable {
+            warnings.push("the primary and all fallbacks are unavailable".to_string());
+        }
+        if let Some(reason) = unverifiable {
+            warnings.push(format!(
+                "the desired weights can't be verified, because {reason}"
+            ));
+        } else if !validate::matches_decision(
+            split,
+            decision,
+            controller.map(|c| c.all_unavailable).unwrap_or_default(),
+        ) {
+            warnings.push(
+                "the current weights differ from those computed for the split; the failover controller may not be running or may lack permissions to patch it".to_string(),
+            );
+        }
+    }
+
+    warnings
+}
+
+pub fn print_description(desc: &Description) {
+    println!("Name:       {}/{}", desc.namespace, desc.name);
+    println!("Apex:       {}", desc.apex);
+    match (&desc.primary, desc.primary_source) {
+        (Some(primary), Some(PrimarySource::Annotation)) => {
+            println!("Primary:    {primary} (from the primary-service annotation)")
+        }
+        (Some(primary), _) => {
+            println!("Primary:    {primary} (implicit: first backend)")
+        }
+        (None, _) => println!("Primary:    <none>"),
+    }
+    match desc.override_service.as_deref() {
+        Some(OVERRIDE_FALLBACKS) => println!("Override:   all ready fallbacks"),
+        Some(service) => println!("Override:   {service}"),
+        None => println!("Override:   <none>"),
+    }
+    match &desc.group {
+        Some(group) if desc.group_members.is_empty() => println!("Group:      {group}"),
+        Some(group) => println!(
+            "Group:      {group} (with {})",
+            desc.group_members.join(", ")
+        ),
+        None => println!("Group:      <none>"),
+    }
+
+    println!();
+    println!("Backends:");
+    let count = |n: Option<usize>| n.map_or_else(|| "-".to_string(), |n| n.to_string());
+    let columns: Vec<Column<BackendDescription>> = vec![
+        Column::new("SERVICE", Box::new(|b| b.service.clone())),
+        Column::new(
+            "EXISTS",
+            Box::new(|b| if b.service_exists { "yes" } else { "no" }.to_string()),
+        ),
+        Column::new("READY", Box::new(move |b| count(b.ready_addresses))),
+        Column::new("NOT_READY", Box::new(move |b| count(b.not_ready_addresses))),
+        Column::new("WEIGHT", Box::new(|b| b.weight.to_string())),
+        Column::new("DESIRED_WEIGHT", Box::new(|b| b.desired_weight.to_string())),
+    ];
+    print!(
+        "{}",
+        Table {
+            cols: columns,
+            data: &desc.backends,
+        }
+    );
+
+    println!();
+    if desc.events.is_empty() {
+        println!("Events:     <none>");
+    } else {
+        println!("Events:");
+        let columns: Vec<Column<EventDescription>> = vec![
+            Column::new("TIME", Box::new(|e| e.time.clone().unwrap_or_default())),
+            Column::new("TYPE", Box::new(|e| e.type_.clone())),
+            Column::new("REASON", Box::new(|e| e.reason.clone())),
+            Column::new("MESSAGE", Box::new(|e| e.message.clone())),
+        ];
+        print!(
+            "{}",
+            Table {
+                cols: columns,
+                data: &desc.events,
+            }
+        );
+    }
+
+    println!();
+    if desc.warnings.is_empty() {
+        println!("Warnings:   <none>");
+    } else {
+        println!("Warnings:");
+        for warning in &desc.warnings {
+            println!("  - {warning}");
+        }
+    }
+}
+
+pub fn json_print_description(desc: &Description) {
+    serde_json::to_writer_pretty(std::io::stdout(), desc).expect("serialization failed");
+    println!();
+}
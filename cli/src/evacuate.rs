@@ -0,0 +1,185 @@
+use crate::{
+    controller,
+    table::{Column, Table},
+    FIELD_MANAGER,
+};
+use anyhow::{bail, Context, Result};
+use k8s_openapi::api::core::v1::Service;
+use kube::{
+    api::{ListParams, Patch, PatchParams},
+    Api, Client, ResourceExt,
+};
+use linkerd_failover_controller::{
+    multicluster::{self, Link, CLUSTER_NAME_LABEL, EVACUATE_ANNOTATION},
+    TrafficSplit,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// A TrafficSplit backend that is a mirror service of an evacuated cluster
+#[derive(Clone, Debug, Serialize)]
+pub struct AffectedBackend {
+    namespace: String,
+    traffic_split: String,
+    service: String,
+}
+
+/// Moves traffic off of all mirror services of `cluster` by annotating its `Link`, returning the
+/// backends of the TrafficSplits matching `selector` that are affected.
+///
+/// This fails if no failover controller has multicluster support enabled. Unless `force` is set,
+/// it also fails if a split would be left without a backend outside of the cluster.
+pub async fn evacuate(
+    client: Client,
+    cluster: &str,
+    multicluster_namespace: &str,
+    selector: &str,
+    force: bool,
+) -> Result<Vec<AffectedBackend>> {
+    let api = Api::<Link>::namespaced(client.clone(), multicluster_namespace);
+    let link = get_link(&api, cluster, multicluster_namespace).await?;
+
+    // Only controllers with multicluster support act on the evacuation annotation.
+    let controllers = controller::controllers(client.clone())
+        .await
+        .context("failed to list failover controllers")?;
+    if !controllers
+        .iter()
+        .any(|c| c.enable_multicluster && c.multicluster_namespace == multicluster_namespace)
+    {
+        bail!("no failover controller has multicluster support enabled for namespace {multicluster_namespace}, so evacuating cluster {cluster} would have no effect; install with --enable-multicluster first");
+    }
+
+    let (affected, stranded) = affected_backends(client, cluster, selector).await?;
+    if !force {
+        if let Some(split) = stranded.first() {
+            bail!("trafficsplit {split} has no backends outside of cluster {cluster}; use --force to evacuate anyway");
+        }
+    }
+
+    set_evacuated(&api, &link, true).await?;
+    Ok(affected)
+}
+
+/// Removes the evacuation annotation from the `Link` of `cluster`, so that its mirror services may
+/// receive traffic again
+pub async fn restore(
+    client: Client,
+    cluster: &str,
+    multicluster_namespace: &str,
+    selector: &str,
+) -> Result<Vec<AffectedBackend>> {
+    let api = Api::<Link>::namespaced(client.clone(), multicluster_namespace);
+    let link = get_link(&api, cluster, multicluster_namespace).await?;
+    if !multicluster::is_evacuated(&link) {
+        bail!("cluster {cluster} is not evacuated");
+    }
+
+    let (affected, _) = affected_backends(client, cluster, selector).await?;
+    set_evacuated(&api, &link, false).await?;
+    Ok(affected)
+}
+
+async fn get_link(api: &Api<Link>, cluster: &str, namespace: &str) -> Result<Link> {
+    let links = api
+        .list(&ListParams::default())
+        .await
+        .with_context(|| format!("failed to list links in namespace {namespace}"))?;
+    let mut links = links
+        .items
+        .into_iter()
+        .filter(|link| link.spec.target_cluster_name == cluster);
+    let link = links
+        .next()
+        .with_context(|| format!("no link to cluster {cluster} found in namespace {namespace}"))?;
+    if let Some(other) = links.next() {
+        bail!(
+            "links {} and {} both target cluster {cluster} in namespace {namespace}",
+            link.name_any(),
+            other.name_any()
+        );
+    }
+    Ok(link)
+}
+
+async fn set_evacuated(api: &Api<Link>, link: &Link, evacuated: bool) -> Result<()> {
+    let patch = serde_json::json!({
+        "metadata": {
+            "annotations": {
+                EVACUATE_ANNOTATION: evacuated.then_some("true"),
+            }
+        }
+    });
+    api.patch(
+        &link.name_any(),
+        &PatchParams {
+            field_manager: Some(FIELD_MANAGER.to_string()),
+            ..Default::default()
+        },
+        &Patch::Merge(patch),
+    )
+    .await
+    .with_context(|| format!("failed to patch link {}", link.name_any()))?;
+    Ok(())
+}
+
+/// Finds the backends of the selected splits that are mirror services of the cluster, along with
+/// the splits whose backends are all in the cluster
+async fn affected_backends(
+    client: Client,
+    cluster: &str,
+    selector: &str,
+) -> Result<(Vec<AffectedBackend>, Vec<String>)> {
+    let mirrors = Api::<Service>::all(client.clone())
+        .list(&ListParams::default().labels(&format!("{CLUSTER_NAME_LABEL}={cluster}")))
+        .await
+        .context("failed to list mirror services")?
+        .items
+        .into_iter()
+        .map(|svc| (svc.namespace().unwrap_or_default(), svc.name_any()))
+        .collect::<HashSet<_>>();
+    let splits = Api::<TrafficSplit>::all(client)
+        .list(&ListParams::default().labels(selector))
+        .await
+        .context("failed to list trafficsplits")?;
+
+    let mut affected = Vec::new();
+    let mut stranded = Vec::new();
+    for split in splits.items {
+        let namespace = split.namespace().unwrap_or_default();
+        let mut remaining = 0;
+        for backend in &split.spec.backends {
+            if mirrors.contains(&(namespace.clone(), backend.service.clone())) {
+                affected.push(AffectedBackend {
+                    namespace: namespace.clone(),
+                    traffic_split: split.name_any(),
+                    service: backend.service.clone(),
+                });
+            } else {
+                remaining += 1;
+            }
+        }
+        if remaining == 0 && !split.spec.backends.is_empty() {
+            stranded.push(format!("{namespace}/{}", split.name_any()));
+        }
+    }
+    Ok((affected, stranded))
+}
+
+pub fn print_affected(backends: &[AffectedBackend]) {
+    let columns: Vec<Column<AffectedBackend>> = vec![
+        Column::new("NAMESPACE", Box::new(|b| b.namespace.clone())),
+        Column::new("TRAFFIC_SPLIT", Box::new(|b| b.traffic_split.clone())),
+        Column::new("BACKEND", Box::new(|b| b.service.clone())),
+    ];
+    let table = Table {
+        cols: columns,
+        data: backends,
+    };
+    print!("{table}");
+}
+
+pub fn json_print_affected(backends: &[AffectedBackend]) {
+    serde_json::to_writer_pretty(std::io::stdout(), &backends).expect("serialization failed");
+    println!();
+}
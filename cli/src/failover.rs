@@ -0,0 +1,193 @@
+use crate::{
+    controller,
+    status::{backend_endpoints, group_endpoints, split_status, TrafficSplitStatus},
+    validate, TrafficSplitRef, CONTROLLED_BY_LABEL, FIELD_MANAGER,
+};
+use anyhow::{bail, Context, Result};
+use k8s_openapi::api::core::v1::Service;
+use kube::{
+    api::{Patch, PatchParams},
+    Api, Client, ResourceExt,
+};
+use linkerd_failover_controller::{
+    endpoints,
+    traffic_split::{self, OVERRIDE_ANNOTATION, OVERRIDE_FALLBACKS},
+    TrafficSplit,
+};
+use std::collections::HashSet;
+use tokio::time;
+
+const POLL_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+/// Moves traffic off of the split's primary by setting the controller's override annotation.
+///
+/// When `to` is set, all traffic is pinned to that backend; otherwise traffic is spread across all
+/// ready fallbacks. Unless `force` is set, this fails if the override would leave the split
+/// without a ready backend.
+pub async fn failover(
+    client: Client,
+    target: &TrafficSplitRef,
+    to: Option<String>,
+    force: bool,
+    timeout: time::Duration,
+) -> Result<Option<TrafficSplitStatus>> {
+    let api = Api::<TrafficSplit>::namespaced(client.clone(), &target.namespace);
+    let split = get_split(&api, target).await?;
+    let primary = primary_service(&split)?;
+    let ready = ready_backends(client.clone(), &split).await?;
+
+    let value = match to {
+        Some(service) => {
+            if !split.spec.backends.iter().any(|b| b.service == service) {
+                bail!("{service} is not a backend of trafficsplit {target}");
+            }
+            if !force && !ready.contains(&service) {
+                bail!("{service} has no ready endpoints; use --force to fail over anyway");
+            }
+            service
+        }
+        None => {
+            let has_ready_fallback = split
+                .spec
+                .backends
+                .iter()
+                .any(|b| b.service != primary && ready.contains(&b.service));
+            if !force && !has_ready_fallback {
+                bail!(
+                    "trafficsplit {target} has no ready fallbacks; use --force to fail over anyway"
+                );
+            }
+            OVERRIDE_FALLBACKS.to_string()
+        }
+    };
+
+    set_override(&api, target, Some(value)).await?;
+    converge(client, &api, target, timeout).await
+}
+
+/// Clears the controller's override annotation so that traffic is routed based on readiness
+/// again.
+pub async fn failback(
+    client: Client,
+    target: &TrafficSplitRef,
+    timeout: time::Duration,
+) -> Result<Option<TrafficSplitStatus>> {
+    let api = Api::<TrafficSplit>::namespaced(client.clone(), &target.namespace);
+    let split = get_split(&api, target).await?;
+    if !split.annotations().contains_key(OVERRIDE_ANNOTATION) {
+        bail!("trafficsplit {target} has no failover override");
+    }
+
+    set_override(&api, target, None).await?;
+    converge(client, &api, target, timeout).await
+}
+
+async fn get_split(api: &Api<TrafficSplit>, target: &TrafficSplitRef) -> Result<TrafficSplit> {
+    api.get_opt(&target.name)
+        .await
+        .with_context(|| format!("failed to get trafficsplit {target}"))?
+        .with_context(|| format!("trafficsplit {target} not found"))
+}
+
+async fn set_override(
+    api: &Api<TrafficSplit>,
+    target: &TrafficSplitRef,
+    value: Option<String>,
+) -> Result<()> {
+    let patch = serde_json::json!({
+        "metadata": {
+            "annotations": {
+                OVERRIDE_ANNOTATION: value,
+            }
+        }
+    });
+    api.patch(
+        &target.name,
+        &PatchParams {
+            field_manager: Some(FIELD_MANAGER.to_string()),
+            ..Default::default()
+        },
+        &Patch::Merge(patch),
+    )
+    .await
+    .with_context(|| format!("failed to patch trafficsplit {target}"))?;
+    Ok(())
+}
+
+/// Waits until the split's weights match those the controller computes for it, and returns the
+/// split's resulting status.
+async fn converge(
+    client: Client,
+    api: &Api<TrafficSplit>,
+    target: &TrafficSplitRef,
+    timeout: time::Duration,
+) -> Result<Option<TrafficSplitStatus>> {
+    let split = get_split(api, target).await?;
+    let controller = controller::controller_for(client.clone(), &split).await?;
+    let services = Api::<Service>::namespaced(client.clone(), &target.namespace);
+    let mut backends = Vec::with_capacity(split.spec.backends.len());
+    for backend in &split.spec.backends {
+        let svc = services
+            .get_opt(&backend.service)
+            .await
+            .with_context(|| format!("failed to get service {}", backend.service))?;
+        backends.extend(svc);
+    }
+    if let Some(reason) = controller
+        .as_ref()
+        .and_then(|c| c.unverifiable(&split, &backends))
+    {
+        eprintln!("Not waiting for the failover controller to update trafficsplit {target}, because {reason}");
+        return Ok(split_status(split));
+    }
+    let selector = controller
+        .as_ref()
+        .map_or(CONTROLLED_BY_LABEL, |c| c.selector.as_str());
+    let default_policy = controller
+        .as_ref()
+        .map(|c| c.all_unavailable)
+        .unwrap_or_default();
+    let wait = async {
+        loop {
+            let split = get_split(api, target).await?;
+            let (members, eps) = group_endpoints(client.clone(), &split, selector).await?;
+            let health = controller::group_health(
+                client.clone(),
+                controller.as_ref(),
+                &target.namespace,
+                eps,
+            )
+            .await?;
+            let decision =
+                traffic_split::decide_group(&split, &members.iter().collect::<Vec<_>>(), &health)
+                    .context("trafficsplit has no backends")?;
+            if validate::matches_decision(&split, &decision, default_policy) {
+                return Ok::<_, anyhow::Error>(split_status(split));
+            }
+            time::sleep(POLL_INTERVAL).await;
+        }
+    };
+
+    match time::timeout(timeout, wait).await {
+        Ok(res) => res,
+        Err(_) => {
+            bail!("timed out waiting for the failover controller to update trafficsplit {target}")
+        }
+    }
+}
+
+fn primary_service(split: &TrafficSplit) -> Result<String> {
+    traffic_split::primary_service(split)
+        .map(|primary| primary.service.to_string())
+        .context("trafficsplit has no backends")
+}
+
+/// Returns the names of the split's backends that have ready endpoints
+async fn ready_backends(client: Client, split: &TrafficSplit) -> Result<HashSet<String>> {
+    let eps = backend_endpoints(client, split).await?;
+    Ok(eps
+        .into_iter()
+        .filter(|(_, ep)| endpoints::is_ready(ep))
+        .map(|(service, _)| service)
+        .collect())
+}
@@ -0,0 +1,178 @@
+use crate::{TrafficSplitRef, CONTROLLED_BY_LABEL, FIELD_MANAGER};
+use anyhow::{bail, Context, Result};
+use k8s_openapi::api::core::v1::Service;
+use kube::{
+    api::{ListParams, ObjectMeta, Patch, PatchParams},
+    Api, Client, ResourceExt,
+};
+use linkerd_failover_controller::{
+    traffic_split::{Backend, TrafficSplitSpec, PRIMARY_SERVICE_ANNOTATION},
+    TrafficSplit,
+};
+use std::collections::{BTreeMap, HashSet};
+
+const MIRRORED_SERVICE_LABEL: &str = "mirror.linkerd.io/mirrored-service";
+const MIRROR_CLUSTER_LABEL: &str = "mirror.linkerd.io/cluster-name";
+
+/// Describes the failover TrafficSplit to generate
+#[derive(Clone, Debug)]
+pub struct SplitOptions {
+    pub target: TrafficSplitRef,
+    /// The service clients send requests to. Defaults to the split's name.
+    pub apex: Option<String>,
+    /// The backend that receives traffic while it is ready. Defaults to the apex.
+    pub primary: Option<String>,
+    pub fallbacks: Vec<String>,
+    /// Adds the multicluster mirrors of the primary as fallbacks
+    pub mirrors: bool,
+    /// The value of the controlled-by label, which must match the controller's selector
+    pub controlled_by: String,
+}
+
+/// Builds a TrafficSplit that sends all traffic to the primary and lists the fallbacks with a
+/// weight of zero, so that the failover controller manages it from the start.
+///
+/// Mirror discovery requires a client; fallbacks must otherwise exist as services when one is
+/// provided.
+pub async fn generate(client: Option<Client>, opts: &SplitOptions) -> Result<TrafficSplit> {
+    let apex = opts
+        .apex
+        .clone()
+        .unwrap_or_else(|| opts.target.name.clone());
+    let primary = opts.primary.clone().unwrap_or_else(|| apex.clone());
+
+    let mut fallbacks = opts.fallbacks.clone();
+    if opts.mirrors {
+        let client = client
+            .clone()
+            .context("discovering mirror services requires access to the cluster")?;
+        let mirrors = mirror_services(client, &opts.target.namespace, &primary).await?;
+        if mirrors.is_empty() {
+            bail!(
+                "no mirror services of {}/{primary} found",
+                opts.target.namespace
+            );
+        }
+        fallbacks.extend(mirrors);
+    }
+    let mut seen = HashSet::new();
+    fallbacks.retain(|f| *f != primary && seen.insert(f.clone()));
+    if fallbacks.is_empty() {
+        bail!("at least one fallback service is required");
+    }
+
+    if let Some(client) = client {
+        let services = Api::<Service>::namespaced(client, &opts.target.namespace);
+        for service in Some(&primary).into_iter().chain(&fallbacks) {
+            let svc = services
+                .get_opt(service)
+                .await
+                .with_context(|| format!("failed to get service {service}"))?;
+            if svc.is_none() {
+                bail!("service {}/{service} does not exist", opts.target.namespace);
+            }
+        }
+    }
+
+    let backends = Some(Backend {
+        service: primary.clone(),
+        weight: 1,
+    })
+    .into_iter()
+    .chain(
+        fallbacks
+            .into_iter()
+            .map(|service| Backend { service, weight: 0 }),
+    )
+    .collect();
+
+    Ok(TrafficSplit {
+        metadata: ObjectMeta {
+            name: Some(opts.target.name.clone()),
+            namespace: Some(opts.target.namespace.clone()),
+            labels: Some(BTreeMap::from([(
+                CONTROLLED_BY_LABEL.to_string(),
+                opts.controlled_by.clone(),
+            )])),
+            annotations: Some(BTreeMap::from([(
+                PRIMARY_SERVICE_ANNOTATION.to_string(),
+                primary,
+            )])),
+            ..Default::default()
+        },
+        spec: TrafficSplitSpec {
+            service: apex,
+            backends,
+        },
+    })
+}
+
+/// Creates the TrafficSplit in the cluster. Unless `force` is set, this fails if the split already
+/// exists, since replacing it resets weights the failover controller may have changed.
+pub async fn apply(client: Client, split: &TrafficSplit, force: bool) -> Result<()> {
+    let namespace = split.namespace().expect("namespace must be set");
+    let api = Api::<TrafficSplit>::namespaced(client, &namespace);
+    if !force {
+        let existing = api.get_opt(&split.name_any()).await.with_context(|| {
+            format!(
+                "failed to get trafficsplit {namespace}/{}",
+                split.name_any()
+            )
+        })?;
+        if existing.is_some() {
+            bail!(
+                "trafficsplit {namespace}/{} already exists; use --force to replace it",
+                split.name_any()
+            );
+        }
+    }
+    api.patch(
+        &split.name_any(),
+        &PatchParams::apply(FIELD_MANAGER).force(),
+        &Patch::Apply(split),
+    )
+    .await
+    .with_context(|| {
+        format!(
+            "failed to apply trafficsplit {namespace}/{}",
+            split.name_any()
+        )
+    })?;
+    Ok(())
+}
+
+/// Lists the services mirrored from other clusters for `service`, which the multicluster service
+/// mirror names `<service>-<cluster>`
+async fn mirror_services(client: Client, namespace: &str, service: &str) -> Result<Vec<String>> {
+    let api = Api::<Service>::namespaced(client, namespace);
+    let params = ListParams::default().labels(&format!("{MIRRORED_SERVICE_LABEL}=true"));
+    let services = api
+        .list(&params)
+        .await
+        .context("failed to list mirror services")?;
+
+    let mut mirrors = services
+        .into_iter()
+        .filter(|svc| {
+            svc.labels()
+                .get(MIRROR_CLUSTER_LABEL)
+                .map_or(false, |cluster| {
+                    svc.name_any() == format!("{service}-{cluster}")
+                })
+        })
+        .map(|svc| svc.name_any())
+        .collect::<Vec<_>>();
+    mirrors.sort();
+    Ok(mirrors)
+}
+
+pub fn print_split(split: &TrafficSplit) -> Result<()> {
+    let manifest = serde_yaml::to_string(split).context("failed to serialize trafficsplit")?;
+    print!("---\n{manifest}");
+    Ok(())
+}
+
+pub fn json_print_split(split: &TrafficSplit) {
+    serde_json::to_writer_pretty(std::io::stdout(), split).expect("serialization failed");
+    println!();
+}
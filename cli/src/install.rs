@@ -0,0 +1,350 @@
+use crate::template::{Release, Renderer};
+use anyhow::{Context, Result};
+use k8s_openapi::api::{
+    core::v1::Namespace,
+    rbac::v1::{ClusterRole, ClusterRoleBinding, RoleBinding},
+};
+use kube::{api::ListParams, Api, Client, Resource, ResourceExt};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+const EXTENSION_LABEL: &str = "linkerd.io/extension";
+const EXTENSION_NAME: &str = "failover";
+const RELEASE_NAME: &str = "linkerd-failover";
+
+const VALUES: &str = include_str!("../../charts/linkerd-failover/values.yaml");
+
+/// The chart templates rendered by `install`, in the order they are emitted. The chart's
+/// namespace-metadata templates are Helm hooks that label the namespace after installation; the
+/// CLI emits a labeled Namespace instead.
+const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "templates/linkerd-failover-rbac.yaml",
+        include_str!("../../charts/linkerd-failover/templates/linkerd-failover-rbac.yaml"),
+    ),
+    (
+        "templates/linkerd-failover-deployment.yaml",
+        include_str!("../../charts/linkerd-failover/templates/linkerd-failover-deployment.yaml"),
+    ),
+];
+
+/// Overrides for the chart's default values. Unset fields keep the chart's defaults.
+#[derive(Clone, Debug, Default)]
+pub struct InstallOptions {
+    pub namespace: String,
+    pub selector: Option<String>,
+    pub log_level: Option<String>,
+    pub log_format: Option<String>,
+    pub image_registry: Option<String>,
+    pub image_name: Option<String>,
+    pub image_tag: Option<String>,
+    pub prometheus_url: Option<String>,
+    pub region: Option<String>,
+    pub zone: Option<String>,
+    pub all_unavailable: Option<String>,
+    pub enable_probes: bool,
+    pub enable_multicluster: bool,
+}
+
+/// Renders the manifests that install the failover extension into `opts.namespace`, as a
+/// multi-document YAML stream suitable for `kubectl apply -f -`.
+pub fn render(opts: &InstallOptions) -> Result<String> {
+    let mut values: Value =
+        serde_yaml::from_str(VALUES).context("failed to parse the chart's values")?;
+    for (path, value) in [
+        ("selector", &opts.selector),
+        ("logLevel", &opts.log_level),
+        ("logFormat", &opts.log_format),
+        ("image.registry", &opts.image_registry),
+        ("image.name", &opts.image_name),
+        ("image.tag", &opts.image_tag),
+        ("prometheusUrl", &opts.prometheus_url),
+        ("region", &opts.region),
+        ("zone", &opts.zone),
+        ("allUnavailable", &opts.all_unavailable),
+    ] {
+        if let Some(value) = value {
+            set_value(&mut values, path, value);
+        }
+    }
+    if opts.enable_probes {
+        values["enableProbes"] = Value::Bool(true);
+    }
+    if opts.enable_multicluster {
+        values["enableMulticluster"] = Value::Bool(true);
+    }
+
+    let release = Release {
+        name: RELEASE_NAME.to_string(),
+        namespace: opts.namespace.clone(),
+    };
+    let templates = TEMPLATES
+        .iter()
+        .map(|(path, src)| (path.to_string(), *src))
+        .collect::<HashMap<_, _>>();
+    let renderer = Renderer::new(&templates, &values, &release);
+
+    let mut docs = vec![serde_json::json!({
+        "apiVersion": "v1",
+        "kind": "Namespace",
+        "metadata": {
+            "name": opts.namespace,
+            "labels": {
+                EXTENSION_LABEL: EXTENSION_NAME,
+            },
+        },
+    })];
+    for (path, _) in TEMPLATES {
+        let rendered = renderer.render(path)?;
+        for doc in serde_yaml::Deserializer::from_str(&rendered) {
+            let mut doc =
+                Value::deserialize(doc).with_context(|| format!("{path} rendered invalid YAML"))?;
+            if doc.is_null() {
+                continue;
+            }
+            // Helm places namespaced resources in the release namespace implicitly; kubectl
+            // would place them in the current context's namespace.
+            if !is_cluster_scoped(&doc) && doc["metadata"].get("namespace").is_none() {
+                doc["metadata"]["namespace"] = Value::String(opts.namespace.clone());
+            }
+            docs.push(doc);
+        }
+    }
+
+    to_yaml_stream(&docs)
+}
+
+/// Renders the manifests of the failover extension's resources found in the cluster, as a
+/// multi-document YAML stream suitable for `kubectl delete -f -`.
+pub async fn render_uninstall(client: Client) -> Result<String> {
+    let params = ListParams::default().labels(&format!("{EXTENSION_LABEL}={EXTENSION_NAME}"));
+
+    let namespaces = Api::<Namespace>::all(client.clone())
+        .list(&params)
+        .await
+        .context("failed to list namespaces")?
+        .items;
+    let deleted = namespaces
+        .iter()
+        .map(|ns| ns.name_any())
+        .collect::<HashSet<_>>();
+
+    let mut docs = Vec::new();
+    for ns in &namespaces {
+        docs.push(manifest(ns));
+    }
+    for cr in Api::<ClusterRole>::all(client.clone())
+        .list(&params)
+        .await
+        .context("failed to list clusterroles")?
+    {
+        docs.push(manifest(&cr));
+    }
+    for crb in Api::<ClusterRoleBinding>::all(client.clone())
+        .list(&params)
+        .await
+        .context("failed to list clusterrolebindings")?
+    {
+        docs.push(manifest(&crb));
+    }
+    // Resources in the extension's namespaces are deleted along with them.
+    for rb in Api::<RoleBinding>::all(client)
+        .list(&params)
+        .await
+        .context("failed to list rolebindings")?
+    {
+        if !rb.namespace().map_or(false, |ns| deleted.contains(&ns)) {
+            docs.push(manifest(&rb));
+        }
+    }
+
+    to_yaml_stream(&docs)
+}
+
+/// Returns the minimal manifest identifying a resource
+fn manifest<K>(obj: &K) -> Value
+where
+    K: Resource<DynamicType = ()>,
+{
+    let mut metadata = serde_json::json!({ "name": obj.name_any() });
+    if let Some(ns) = obj.namespace() {
+        metadata["namespace"] = Value::String(ns);
+    }
+    serde_json::json!({
+        "apiVersion": K::api_version(&()),
+        "kind": K::kind(&()),
+        "metadata": metadata,
+    })
+}
+
+fn is_cluster_scoped(doc: &Value) -> bool {
+    matches!(
+        doc["kind"].as_str(),
+        Some("Namespace" | "ClusterRole" | "ClusterRoleBinding")
+    )
+}
+
+fn set_value(values: &mut Value, path: &str, value: &str) {
+    let target = path.split('.').fold(values, |v, key| {
+        if !v.is_object() {
+            *v = Value::Object(Default::default());
+        }
+        &mut v[key]
+    });
+    *target = Value::String(value.to_string());
+}
+
+fn to_yaml_stream(docs: &[Value]) -> Result<String> {
+    let mut out = String::new();
+    for doc in docs {
+        out.push_str("---\n");
+        out.push_str(&serde_yaml::to_string(doc).context("failed to serialize manifest")?);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::{apps::v1::Deployment, core::v1::ServiceAccount};
+
+    /// Renders the full chart and parses every document as the resource its kind names
+    fn render_docs(opts: &InstallOptions) -> Vec<Value> {
+        let rendered = render(opts).unwrap();
+        let docs = serde_yaml::Deserializer::from_str(&rendered)
+            .map(|doc| Value::deserialize(doc).unwrap())
+            .collect::<Vec<_>>();
+        for doc in &docs {
+            let kind = doc["kind"].as_str().unwrap_or_default();
+            let parsed = match kind {
+                "Namespace" => serde_json::from_value::<Namespace>(doc.clone()).map(drop),
+                "ServiceAccount" => serde_json::from_value::<ServiceAccount>(doc.clone()).map(drop),
+                "ClusterRole" => serde_json::from_value::<ClusterRole>(doc.clone()).map(drop),
+                "ClusterRoleBinding" => {
+                    serde_json::from_value::<ClusterRoleBinding>(doc.clone()).map(drop)
+                }
+                "Deployment" => serde_json::from_value::<Deployment>(doc.clone()).map(drop),
+                kind => panic!("unexpected kind {:?} in {}", kind, doc),
+            };
+            parsed.unwrap_or_else(|error| panic!("invalid {}: {}", kind, error));
+            if !is_cluster_scoped(doc) {
+                assert_eq!(doc["metadata"]["namespace"], opts.namespace.as_str());
+            }
+        }
+        docs
+    }
+
+    fn controller_args(docs: &[Value]) -> Vec<String> {
+        let deploy = docs
+            .iter()
+            .find(|doc| doc["kind"] == "Deployment")
+            .expect("the chart must render a Deployment");
+        let deploy = serde_json::from_value::<Deployment>(deploy.clone()).unwrap();
+        deploy.spec.unwrap().template.spec.unwrap().containers[0]
+            .args
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// Given the chart's default values, every rendered document is a valid resource and the
+    /// controller runs with the chart's defaults.
+    #[test]
+    fn renders_chart_with_default_values() {
+        let docs = render_docs(&InstallOptions {
+            namespace: "linkerd-failover".to_string(),
+            ..Default::default()
+        });
+        let kinds = docs
+            .iter()
+            .map(|doc| doc["kind"].as_str().unwrap())
+            .collect::<HashSet<_>>();
+        for kind in ["Namespace", "ServiceAccount", "ClusterRole", "Deployment"] {
+            assert!(kinds.contains(kind), "missing {} in {:?}", kind, kinds);
+        }
+
+        let args = controller_args(&docs);
+        assert!(
+            args.contains(
+                &"--selector=failover.linkerd.io/controlled-by=linkerd-failover".to_string()
+            ),
+            "{:?}",
+            args
+        );
+        assert!(
+            args.contains(&"--field-manager=failover.linkerd.io/linkerd-failover".to_string()),
+            "{:?}",
+            args
+        );
+        assert!(
+            args.contains(&"--all-unavailable=zero".to_string()),
+            "{:?}",
+            args
+        );
+        assert!(
+            !args.iter().any(|a| a.starts_with("--prometheus-url")),
+            "{:?}",
+            args
+        );
+        assert!(
+            !args.contains(&"--enable-multicluster".to_string()),
+            "{:?}",
+            args
+        );
+    }
+
+    /// Given every install option, every rendered document is a valid resource and the controller
+    /// runs with the given options.
+    #[test]
+    fn renders_chart_with_all_options() {
+        let docs = render_docs(&InstallOptions {
+            namespace: "failover".to_string(),
+            selector: Some("app=web".to_string()),
+            log_level: Some("debug".to_string()),
+            log_format: Some("json".to_string()),
+            image_registry: Some("registry.example.com".to_string()),
+            image_name: Some("failover".to_string()),
+            image_tag: Some("dev".to_string()),
+            prometheus_url: Some("http://prometheus.linkerd-viz:9090".to_string()),
+            region: Some("us-east".to_string()),
+            zone: Some("us-east-1a".to_string()),
+            all_unavailable: Some("keep".to_string()),
+            enable_probes: true,
+            enable_multicluster: true,
+        });
+
+        let args = controller_args(&docs);
+        for arg in [
+            "--selector=app=web",
+            "--log-level=debug",
+            "--log-format=json",
+            "--field-manager=failover.linkerd.io/failover",
+            "--prometheus-url=http://prometheus.linkerd-viz:9090",
+            "--enable-probes",
+            "--enable-multicluster",
+            "--region=us-east",
+            "--zone=us-east-1a",
+            "--all-unavailable=keep",
+        ] {
+            assert!(
+                args.contains(&arg.to_string()),
+                "missing {} in {:?}",
+                arg,
+                args
+            );
+        }
+        let rendered = render(&InstallOptions {
+            namespace: "failover".to_string(),
+            image_registry: Some("registry.example.com".to_string()),
+            image_name: Some("failover".to_string()),
+            image_tag: Some("dev".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(
+            rendered.contains("registry.example.com/failover:dev"),
+            "{}",
+            rendered
+        );
+    }
+}
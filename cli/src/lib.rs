@@ -1,3 +1,52 @@
 pub mod check;
+mod controller;
+pub mod describe;
+pub mod evacuate;
+pub mod failover;
+pub mod generate;
+pub mod install;
+pub mod simulate;
 pub mod status;
 mod table;
+mod template;
+mod validate;
+
+use anyhow::{bail, Error};
+use std::{fmt, str::FromStr};
+
+/// Selects the TrafficSplits managed by the failover controller
+const CONTROLLED_BY_LABEL: &str = linkerd_failover_controller::DEFAULT_SELECTOR;
+
+/// The field manager used when the CLI modifies TrafficSplits
+const FIELD_MANAGER: &str = "linkerd-failover-cli";
+
+/// Identifies a TrafficSplit as `<namespace>/<name>`
+#[derive(Clone, Debug)]
+pub struct TrafficSplitRef {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl FromStr for TrafficSplitRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((namespace, name))
+                if !namespace.is_empty() && !name.is_empty() && !name.contains('/') =>
+            {
+                Ok(Self {
+                    namespace: namespace.to_string(),
+                    name: name.to_string(),
+                })
+            }
+            _ => bail!("expected a TrafficSplit as <namespace>/<name>, got {s:?}"),
+        }
+    }
+}
+
+impl fmt::Display for TrafficSplitRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.name)
+    }
+}
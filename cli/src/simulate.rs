@@ -0,0 +1,426 @@
+use crate::table::{Column, Table};
+use anyhow::{bail, Context, Result};
+use k8s_openapi::api::core::v1::{EndpointAddress, EndpointSubset};
+use kube::{
+    api::ObjectMeta,
+    runtime::{reflector::store::Writer, watcher::Event},
+    ResourceExt,
+};
+use linkerd_failover_controller::{
+    endpoints,
+    traffic_split::{
+        self, Backend, FailoverUpdate, ALL_BACKENDS_UNAVAILABLE, COMPETING_CONTROLLER,
+        DEFAULT_FIELD_MANAGER, FAILOVER,
+    },
+    Ctx, Endpoints, TrafficSplit,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+const DEFAULT_NAMESPACE: &str = "default";
+
+/// Bounds the number of patches the controller may issue in response to a single change, so that
+/// a split that never settles fails the simulation instead of looping forever
+const MAX_PATCHES: usize = 1000;
+
+/// A scripted sequence of changes, read from a YAML file
+#[derive(Debug, Default, Deserialize)]
+pub struct Timeline {
+    pub steps: Vec<Step>,
+}
+
+/// Changes applied at an offset from the start of the simulation
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Step {
+    /// Offset from the start of the simulation, e.g. `30s`. Steps must be in order.
+    pub at: String,
+    /// Namespace of the services named by `ready`, `notReady`, and `deleted`
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// Services whose endpoints become ready
+    #[serde(default)]
+    pub ready: Vec<String>,
+    /// Services whose endpoints become not ready
+    #[serde(default)]
+    pub not_ready: Vec<String>,
+    /// Services whose endpoints are deleted
+    #[serde(default)]
+    pub deleted: Vec<String>,
+    /// TrafficSplit or Endpoints resources that are created or updated
+    #[serde(default)]
+    pub manifests: Vec<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Simulation {
+    pub steps: Vec<StepResult>,
+}
+
+/// The state of all TrafficSplits after a step, and the events recorded while processing it
+#[derive(Debug, Serialize)]
+pub struct StepResult {
+    pub at: String,
+    pub events: Vec<SimulatedEvent>,
+    pub traffic_splits: Vec<SplitWeights>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SimulatedEvent {
+    pub traffic_split: String,
+    #[serde(rename = "type")]
+    pub type_: &'static str,
+    pub reason: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SplitWeights {
+    pub namespace: String,
+    pub name: String,
+    pub backends: Vec<Backend>,
+}
+
+#[derive(Default)]
+struct Resources {
+    traffic_splits: Vec<TrafficSplit>,
+    endpoints: Vec<Endpoints>,
+}
+
+/// Runs the controller's watch handlers against the TrafficSplits and Endpoints in `manifests`,
+/// then against each step of the timeline. Time is simulated, so the timeline's offsets elapse
+/// instantly.
+pub fn simulate(manifests: &[PathBuf], timeline: Option<&Path>) -> Result<Simulation> {
+    let mut resources = Resources::default();
+    for path in manifests {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        for doc in serde_yaml::Deserializer::from_str(&contents) {
+            let doc = Value::deserialize(doc)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            resources
+                .add(doc)
+                .with_context(|| format!("invalid manifest in {}", path.display()))?;
+        }
+    }
+
+    let timeline = match timeline {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            serde_yaml::from_str(&contents)
+                .with_context(|| format!("failed to parse timeline {}", path.display()))?
+        }
+        None => Timeline::default(),
+    };
+
+    // The simulation drives the controller's handlers to completion on a runtime of its own, so that
+    // it can be run from within the CLI's runtime.
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .context("failed to build runtime")?;
+        rt.block_on(run(resources, timeline))
+    })
+    .join()
+    .expect("simulation panicked")
+}
+
+async fn run(resources: Resources, timeline: Timeline) -> Result<Simulation> {
+    let mut sim = Simulator::new();
+    let mut steps = Vec::new();
+
+    // Like the controller, start from a full listing of each resource.
+    sim.apply_endpoints(Event::Restarted(resources.endpoints))
+        .await?;
+    sim.apply_split(Event::Restarted(resources.traffic_splits))
+        .await?;
+    steps.push(sim.step_result(Duration::ZERO));
+
+    // The timeline runs on a simulated clock: each step happens at its offset from the start, as
+    // soon as the controller has handled the previous one.
+    let mut last = Duration::ZERO;
+    for step in timeline.steps {
+        let at = humantime::parse_duration(&step.at)
+            .with_context(|| format!("invalid step offset {:?}", step.at))?;
+        if at < last {
+            bail!(
+                "timeline steps must be in order, but {} follows {}",
+                step.at,
+                humantime::format_duration(last)
+            );
+        }
+        last = at;
+
+        let mut resources = Resources::default();
+        for doc in step.manifests {
+            resources.add(doc)?;
+        }
+        for split in resources.traffic_splits {
+            sim.apply_split(Event::Applied(split)).await?;
+        }
+        for ep in resources.endpoints {
+            sim.apply_endpoints(Event::Applied(ep)).await?;
+        }
+        for service in step.deleted {
+            let ep = mk_endpoints(&step.namespace, service, Vec::new(), Vec::new());
+            sim.apply_endpoints(Event::Deleted(ep)).await?;
+        }
+        for service in step.ready {
+            let ep = mk_endpoints(&step.namespace, service, vec![address()], Vec::new());
+            sim.apply_endpoints(Event::Applied(ep)).await?;
+        }
+        for service in step.not_ready {
+            let ep = mk_endpoints(&step.namespace, service, Vec::new(), vec![address()]);
+            sim.apply_endpoints(Event::Applied(ep)).await?;
+        }
+
+        steps.push(sim.step_result(at));
+    }
+
+    Ok(Simulation { steps })
+}
+
+/// Holds the controller's stores, feeding its watch handlers as the reflectors would and applying
+/// its patches as the API server would
+struct Simulator {
+    ctx: Ctx,
+    endpoints: Writer<Endpoints>,
+    traffic_splits: Writer<TrafficSplit>,
+    patches: mpsc::Receiver<FailoverUpdate>,
+    events: Vec<SimulatedEvent>,
+}
+
+impl Simulator {
+    fn new() -> Self {
+        let endpoints = Writer::default();
+        let traffic_splits = Writer::default();
+        let (tx, patches) = mpsc::channel(MAX_PATCHES);
+        let ctx = Ctx {
+            health: Arc::new(endpoints.as_reader()),
+            traffic_splits: traffic_splits.as_reader(),
+            patches: tx,
+            field_manager: DEFAULT_FIELD_MANAGER.to_string(),
+            all_unavailable: Default::default(),
+            unavailable: Default::default(),
+        };
+        Self {
+            ctx,
+            endpoints,
+            traffic_splits,
+            patches,
+            events: Vec::new(),
+        }
+    }
+
+    async fn apply_endpoints(&mut self, ev: Event<Endpoints>) -> Result<()> {
+        self.endpoints.apply_watcher_event(&ev);
+        endpoints::handle(ev, &self.ctx).await;
+        self.apply_patches().await
+    }
+
+    async fn apply_split(&mut self, ev: Event<TrafficSplit>) -> Result<()> {
+        self.traffic_splits.apply_watcher_event(&ev);
+        traffic_split::handle(ev, &self.ctx).await;
+        self.apply_patches().await
+    }
+
+    /// Applies the controller's pending patches, notifying it of each patched split just as its
+    /// watch would, until it stops issuing patches
+    async fn apply_patches(&mut self) -> Result<()> {
+        let mut applied = 0;
+        while let Ok(update) = self.patches.try_recv() {
+            applied += 1;
+            if applied > MAX_PATCHES {
+                bail!("the controller did not stop patching trafficsplits");
+            }
+
+            let target = format!(
+                "{}/{}",
+                update.target.namespace.as_deref().unwrap_or_default(),
+                update.target.name
+            );
+            if let Some(message) = update.competing_managers_note() {
+                self.events.push(SimulatedEvent {
+                    traffic_split: target.clone(),
+                    type_: "Warning",
+                    reason: COMPETING_CONTROLLER,
+                    message,
+                });
+            }
+            match update.all_unavailable_note() {
+                Some(message) => self.events.push(SimulatedEvent {
+                    traffic_split: target,
+                    type_: "Warning",
+                    reason: ALL_BACKENDS_UNAVAILABLE,
+                    message,
+                }),
+                None => self.events.push(SimulatedEvent {
+                    traffic_split: target,
+                    type_: "Normal",
+                    reason: FAILOVER,
+                    message: update.event_note(),
+                }),
+            }
+
+            let mut split = match self.traffic_splits.as_reader().get(&update.target) {
+                Some(split) => (*split).clone(),
+                None => continue,
+            };
+            split.spec.backends = update.backends;
+            let ev = Event::Applied(split);
+            self.traffic_splits.apply_watcher_event(&ev);
+            traffic_split::handle(ev, &self.ctx).await;
+        }
+        Ok(())
+    }
+
+    fn step_result(&mut self, at: Duration) -> StepResult {
+        let mut traffic_splits = self
+            .traffic_splits
+            .as_reader()
+            .state()
+            .iter()
+            .map(|split| SplitWeights {
+                namespace: split.namespace().unwrap_or_default(),
+                name: split.name_any(),
+                backends: split.spec.backends.clone(),
+            })
+            .collect::<Vec<_>>();
+        traffic_splits.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+        StepResult {
+            at: humantime::format_duration(at).to_string(),
+            events: std::mem::take(&mut self.events),
+            traffic_splits,
+        }
+    }
+}
+
+impl Resources {
+    /// Adds a TrafficSplit or Endpoints resource, or the items of a List
+    fn add(&mut self, mut doc: Value) -> Result<()> {
+        if doc.is_null() {
+            return Ok(());
+        }
+        if doc["metadata"].get("namespace").is_none() {
+            doc["metadata"]["namespace"] = Value::String(DEFAULT_NAMESPACE.to_string());
+        }
+        match doc["kind"].as_str() {
+            Some("TrafficSplit") => self
+                .traffic_splits
+                .push(serde_json::from_value(doc).context("invalid trafficsplit")?),
+            Some("Endpoints") => self
+                .endpoints
+                .push(serde_json::from_value(doc).context("invalid endpoints")?),
+            Some("List") => {
+                if let Value::Array(items) = doc["items"].take() {
+                    for item in items {
+                        self.add(item)?;
+                    }
+                }
+            }
+            Some(kind) => bail!("unsupported kind {kind}; expected TrafficSplit or Endpoints"),
+            None => bail!("manifest has no kind"),
+        }
+        Ok(())
+    }
+}
+
+fn mk_endpoints(
+    namespace: &str,
+    service: String,
+    addresses: Vec<EndpointAddress>,
+    not_ready_addresses: Vec<EndpointAddress>,
+) -> Endpoints {
+    Endpoints {
+        metadata: ObjectMeta {
+            name: Some(service),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+        subsets: Some(vec![EndpointSubset {
+            addresses: Some(addresses),
+            not_ready_addresses: Some(not_ready_addresses),
+            ..Default::default()
+        }]),
+    }
+}
+
+fn address() -> EndpointAddress {
+    EndpointAddress {
+        ip: "10.0.0.1".to_string(),
+        ..Default::default()
+    }
+}
+
+fn default_namespace() -> String {
+    DEFAULT_NAMESPACE.to_string()
+}
+
+pub fn print_simulation(sim: &Simulation) {
+    for (i, step) in sim.steps.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("At {}:", step.at);
+
+        let rows = step
+            .traffic_splits
+            .iter()
+            .flat_map(|s| {
+                s.backends
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, b)| (if i == 0 { Some(s) } else { None }, b))
+            })
+            .collect::<Vec<_>>();
+        let columns: Vec<Column<WeightRow<'_>>> = vec![
+            Column::new(
+                "TRAFFIC_SPLIT",
+                Box::new(|(s, _)| {
+                    s.map(|s| format!("{}/{}", s.namespace, s.name))
+                        .unwrap_or_default()
+                }),
+            ),
+            Column::new("BACKEND", Box::new(|(_, b)| b.service.clone())),
+            Column::new("WEIGHT", Box::new(|(_, b)| b.weight.to_string())),
+        ];
+        print!(
+            "{}",
+            Table {
+                cols: columns,
+                data: &rows,
+            }
+        );
+
+        if !step.events.is_empty() {
+            println!();
+            let columns: Vec<Column<SimulatedEvent>> = vec![
+                Column::new("TYPE", Box::new(|e| e.type_.to_string())),
+                Column::new("REASON", Box::new(|e| e.reason.to_string())),
+                Column::new("MESSAGE", Box::new(|e| e.message.clone())),
+            ];
+            print!(
+                "{}",
+                Table {
+                    cols: columns,
+                    data: &step.events,
+                }
+            );
+        }
+    }
+}
+
+/// A backend row in the simulation table. The split is only set on the split's first row.
+type WeightRow<'a> = (Option<&'a SplitWeights>, &'a Backend);
+
+pub fn json_print_simulation(sim: &Simulation) {
+    serde_json::to_writer_pretty(std::io::stdout(), sim).expect("serialization failed");
+    println!();
+}
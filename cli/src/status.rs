@@ -1,19 +1,62 @@
 use crate::table::{Column, Table};
 use anyhow::{Context, Result};
-use kube::{api::ListParams, Api, Client, ResourceExt};
-use linkerd_failover_controller::TrafficSplit;
+use futures::prelude::*;
+use k8s_openapi::api::core::v1::Endpoints;
+use kube::{
+    api::ListParams,
+    runtime::{watcher, WatchStreamExt},
+    Api, Client, ResourceExt,
+};
+use linkerd_failover_controller::{endpoints, traffic_split, TrafficSplit};
 use serde::Serialize;
-use std::fmt::Display;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    io::IsTerminal,
+    time::SystemTime,
+};
 
-#[derive(Serialize)]
+#[derive(Clone, PartialEq, Serialize)]
 pub struct TrafficSplitStatus {
     namespace: String,
     name: String,
     status: FailoverStatus,
+    primary: String,
+    /// Set when the split has no primary annotation, so its first backend is the primary
+    primary_implicit: bool,
+    /// The split's failover group, if it has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
     services: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backends: Option<Vec<BackendStatus>>,
 }
 
-#[derive(Serialize)]
+/// The weight and endpoint readiness of a TrafficSplit backend
+#[derive(Clone, PartialEq, Serialize)]
+struct BackendStatus {
+    service: String,
+    weight: u32,
+    /// Unset if the backend has no `Endpoints` resource
+    ready_addresses: Option<usize>,
+    not_ready_addresses: Option<usize>,
+}
+
+impl TrafficSplitStatus {
+    fn primary_label(&self) -> String {
+        if self.primary_implicit {
+            format!("{} (implicit)", self.primary)
+        } else {
+            self.primary.clone()
+        }
+    }
+
+    fn group_label(&self) -> String {
+        self.group.clone().unwrap_or_else(|| "-".to_string())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase")]
 enum FailoverStatus {
     Primary,
@@ -30,39 +73,147 @@ impl Display for FailoverStatus {
     }
 }
 
-pub async fn status(client: Client, label_selector: &str) -> Result<Vec<TrafficSplitStatus>> {
-    let api = Api::<TrafficSplit>::all(client);
+/// Lists the statuses of the TrafficSplits matching the selector. When `wide` is set, the
+/// `Endpoints` in each of the splits' namespaces are listed to report each backend's readiness.
+pub async fn status(
+    client: Client,
+    label_selector: &str,
+    wide: bool,
+) -> Result<Vec<TrafficSplitStatus>> {
+    let api = Api::<TrafficSplit>::all(client.clone());
     let list_params = ListParams::default().labels(label_selector);
     let traffic_splits = api
         .list(&list_params)
         .await
         .context("failed to list TrafficSplits")?;
-    let statuses = traffic_splits
+
+    let mut eps_by_namespace = HashMap::<String, HashMap<String, Endpoints>>::new();
+    let mut statuses = Vec::with_capacity(traffic_splits.items.len());
+    for ts in traffic_splits.items {
+        let backends = if wide {
+            let namespace = ts.namespace().expect("TrafficSplits must be namespaced");
+            if !eps_by_namespace.contains_key(&namespace) {
+                let eps = namespace_endpoints(client.clone(), &namespace).await?;
+                eps_by_namespace.insert(namespace.clone(), eps);
+            }
+            let eps = &eps_by_namespace[&namespace];
+            Some(
+                ts.spec
+                    .backends
+                    .iter()
+                    .map(|b| {
+                        let ep = eps.get(&b.service);
+                        BackendStatus {
+                            service: b.service.clone(),
+                            weight: b.weight,
+                            ready_addresses: ep.map(endpoints::ready_addresses),
+                            not_ready_addresses: ep.map(endpoints::not_ready_addresses),
+                        }
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        if let Some(mut status) = split_status(ts) {
+            status.backends = backends;
+            statuses.push(status);
+        }
+    }
+    Ok(statuses)
+}
+
+/// Lists the `Endpoints` in the namespace, keyed by service name
+async fn namespace_endpoints(
+    client: Client,
+    namespace: &str,
+) -> Result<HashMap<String, Endpoints>> {
+    let eps = Api::<Endpoints>::namespaced(client, namespace)
+        .list(&ListParams::default())
+        .await
+        .with_context(|| format!("failed to list endpoints in {namespace}"))?;
+    Ok(eps
         .items
         .into_iter()
-        .flat_map(|ts| {
-            let primary = ts
-                .metadata
-                .annotations
-                .as_ref()
-                .and_then(|annotations| annotations.get("failover.linkerd.io/primary-service"));
-            primary.map(|primary| {
-                let active_backends = active_backends(&ts);
-                let status = if active_backends.contains(primary) {
-                    FailoverStatus::Primary
-                } else {
-                    FailoverStatus::Fallback
-                };
-                TrafficSplitStatus {
-                    namespace: ts.namespace().expect("TrafficSplits must be namespaced"),
-                    name: ts.name_any(),
-                    status,
-                    services: active_backends,
-                }
-            })
-        })
-        .collect();
-    Ok(statuses)
+        .map(|ep| (ep.name_any(), ep))
+        .collect())
+}
+
+/// Fetches the `Endpoints` of each of the split's backends, keyed by service name. Backends
+/// without an `Endpoints` resource are omitted.
+pub(crate) async fn backend_endpoints(
+    client: Client,
+    ts: &TrafficSplit,
+) -> Result<HashMap<String, Endpoints>> {
+    let namespace = ts.namespace().expect("TrafficSplits must be namespaced");
+    let api = Api::<Endpoints>::namespaced(client, &namespace);
+    let mut eps = HashMap::with_capacity(ts.spec.backends.len());
+    for backend in &ts.spec.backends {
+        let ep = api
+            .get_opt(&backend.service)
+            .await
+            .with_context(|| format!("failed to get endpoints {namespace}/{}", backend.service))?;
+        if let Some(ep) = ep {
+            eps.insert(backend.service.clone(), ep);
+        }
+    }
+    Ok(eps)
+}
+
+/// Fetches the members of the split's failover group, including the split itself, along with the
+/// `Endpoints` of all of their backends, so that the controller's decision can be reproduced.
+/// Only splits matching the controller's `selector` are considered members, as the controller
+/// does.
+pub(crate) async fn group_endpoints(
+    client: Client,
+    ts: &TrafficSplit,
+    selector: &str,
+) -> Result<(Vec<TrafficSplit>, HashMap<String, Endpoints>)> {
+    let members = if traffic_split::group(ts).is_some() {
+        let namespace = ts.namespace().expect("TrafficSplits must be namespaced");
+        let splits = Api::<TrafficSplit>::namespaced(client.clone(), &namespace)
+            .list(&ListParams::default().labels(selector))
+            .await
+            .context("failed to list TrafficSplits")?
+            .items;
+        let mut members = traffic_split::group_members(ts, &splits)
+            .into_iter()
+            .filter(|m| m.name_any() != ts.name_any())
+            .cloned()
+            .collect::<Vec<_>>();
+        members.insert(0, ts.clone());
+        members
+    } else {
+        vec![ts.clone()]
+    };
+
+    let mut eps = HashMap::new();
+    for member in &members {
+        eps.extend(backend_endpoints(client.clone(), member).await?);
+    }
+    Ok((members, eps))
+}
+
+/// Summarizes the failover state of a TrafficSplit. The primary service is resolved the same way
+/// the failover controller resolves it. Returns `None` if the split has no backends.
+pub(crate) fn split_status(ts: TrafficSplit) -> Option<TrafficSplitStatus> {
+    let primary = traffic_split::primary_service(&ts)?;
+    let active_backends = active_backends(&ts);
+    let status = if active_backends.iter().any(|s| s == primary.service) {
+        FailoverStatus::Primary
+    } else {
+        FailoverStatus::Fallback
+    };
+    Some(TrafficSplitStatus {
+        namespace: ts.namespace().expect("TrafficSplits must be namespaced"),
+        name: ts.name_any(),
+        status,
+        primary: primary.service.to_string(),
+        primary_implicit: primary.is_implicit(),
+        group: traffic_split::group(&ts).map(str::to_string),
+        services: active_backends,
+        backends: None,
+    })
 }
 
 pub fn print_status(results: &[TrafficSplitStatus]) {
@@ -70,6 +221,8 @@ pub fn print_status(results: &[TrafficSplitStatus]) {
         Column::new("NAMESPACE", Box::new(|r| r.namespace.clone())),
         Column::new("TRAFFIC_SPLIT", Box::new(|r| r.name.clone())),
         Column::new("STATUS", Box::new(|r| r.status.to_string())),
+        Column::new("PRIMARY", Box::new(|r| r.primary_label())),
+        Column::new("GROUP", Box::new(|r| r.group_label())),
         Column::new("ACTIVE_BACKENDS", Box::new(|r| r.services.join(", "))),
     ];
     let table = Table {
@@ -79,11 +232,243 @@ pub fn print_status(results: &[TrafficSplitStatus]) {
     print!("{table}");
 }
 
+/// Prints a row per backend, showing its weight and endpoint readiness next to the split's status.
+pub fn wide_print_status(results: &[TrafficSplitStatus]) {
+    let rows = results
+        .iter()
+        .flat_map(|r| {
+            let backends = r.backends.as_deref().unwrap_or_default();
+            backends
+                .iter()
+                .enumerate()
+                .map(move |(i, b)| (if i == 0 { Some(r) } else { None }, b))
+        })
+        .collect::<Vec<_>>();
+    let split = |f: fn(&TrafficSplitStatus) -> String| -> Box<dyn Fn(&WideRow<'_>) -> String> {
+        Box::new(move |(r, _)| r.map(f).unwrap_or_default())
+    };
+    let count = |n: Option<usize>| n.map_or_else(|| "-".to_string(), |n| n.to_string());
+    let columns: Vec<Column<WideRow<'_>>> = vec![
+        Column::new("NAMESPACE", split(|r| r.namespace.clone())),
+        Column::new("TRAFFIC_SPLIT", split(|r| r.name.clone())),
+        Column::new("STATUS", split(|r| r.status.to_string())),
+        Column::new("PRIMARY", split(|r| r.primary_label())),
+        Column::new("GROUP", split(|r| r.group_label())),
+        Column::new("BACKEND", Box::new(|(_, b)| b.service.clone())),
+        Column::new("WEIGHT", Box::new(|(_, b)| b.weight.to_string())),
+        Column::new("READY", Box::new(move |(_, b)| count(b.ready_addresses))),
+        Column::new(
+            "NOT_READY",
+            Box::new(move |(_, b)| count(b.not_ready_addresses)),
+        ),
+    ];
+    let table = Table {
+        cols: columns,
+        data: &rows,
+    };
+    print!("{table}");
+}
+
+/// A backend row in the wide status table. The split is only set on the split's first row.
+type WideRow<'a> = (Option<&'a TrafficSplitStatus>, &'a BackendStatus);
+
 pub fn json_print_status(results: &[TrafficSplitStatus]) {
     serde_json::to_writer_pretty(std::io::stdout(), &results).expect("serialization failed");
     println!();
 }
 
+/// A change to a watched TrafficSplit's status
+#[derive(Serialize)]
+struct StatusChange {
+    event: ChangeEvent,
+    #[serde(flatten)]
+    status: TrafficSplitStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_status: Option<FailoverStatus>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ChangeEvent {
+    Updated,
+    Deleted,
+}
+
+/// A watched TrafficSplit's status along with its most recent transition
+struct WatchedStatus {
+    status: TrafficSplitStatus,
+    transition: Option<String>,
+    /// Set if the split's active backends changed since the table was last rendered
+    changed: bool,
+}
+
+type Key = (String, String);
+
+/// Re-renders the status table whenever a TrafficSplit matching the selector changes. Rows whose
+/// status has transitioned between primary and fallback show when that happened, and rows whose
+/// active backends changed since the last refresh are marked with a `*`.
+pub async fn watch_print_status(client: Client, label_selector: &str) -> Result<()> {
+    let mut rows = BTreeMap::<Key, WatchedStatus>::new();
+    let clear = std::io::stdout().is_terminal();
+    watch(client, label_selector, |changes| {
+        for row in rows.values_mut() {
+            row.changed = false;
+        }
+        for change in changes {
+            let key = (change.status.namespace.clone(), change.status.name.clone());
+            match change.event {
+                ChangeEvent::Deleted => {
+                    rows.remove(&key);
+                }
+                ChangeEvent::Updated => {
+                    let transition = match change.previous_status {
+                        Some(previous) if previous != change.status.status => Some(format!(
+                            "{} -> {} ({})",
+                            previous,
+                            change.status.status,
+                            humantime::format_rfc3339_seconds(SystemTime::now())
+                        )),
+                        _ => rows.get(&key).and_then(|r| r.transition.clone()),
+                    };
+                    let changed = rows
+                        .get(&key)
+                        .map_or(false, |r| r.status.services != change.status.services);
+                    rows.insert(
+                        key,
+                        WatchedStatus {
+                            status: change.status,
+                            transition,
+                            changed,
+                        },
+                    );
+                }
+            }
+        }
+
+        let data = rows.values().collect::<Vec<_>>();
+        let columns: Vec<Column<&WatchedStatus>> = vec![
+            Column::new("NAMESPACE", Box::new(|r| r.status.namespace.clone())),
+            Column::new("TRAFFIC_SPLIT", Box::new(|r| r.status.name.clone())),
+            Column::new("STATUS", Box::new(|r| r.status.status.to_string())),
+            Column::new("PRIMARY", Box::new(|r| r.status.primary_label())),
+            Column::new("GROUP", Box::new(|r| r.status.group_label())),
+            Column::new(
+                "ACTIVE_BACKENDS",
+                Box::new(|r| {
+                    let services = r.status.services.join(", ");
+                    if r.changed {
+                        format!("{services} *")
+                    } else {
+                        services
+                    }
+                }),
+            ),
+            Column::new(
+                "LAST_TRANSITION",
+                Box::new(|r| r.transition.clone().unwrap_or_default()),
+            ),
+        ];
+        let table = Table {
+            cols: columns,
+            data: &data,
+        };
+        if clear {
+            // Clear the screen and move the cursor to the top-left corner.
+            print!("\x1b[2J\x1b[H");
+        } else {
+            println!();
+        }
+        print!("{table}");
+    })
+    .await
+}
+
+/// Prints a JSON object per line whenever a TrafficSplit matching the selector changes.
+pub async fn watch_json_print_status(client: Client, label_selector: &str) -> Result<()> {
+    watch(client, label_selector, |changes| {
+        for change in changes {
+            serde_json::to_writer(std::io::stdout(), &change).expect("serialization failed");
+            println!();
+        }
+    })
+    .await
+}
+
+/// Watches TrafficSplits matching the selector, invoking `on_change` with the statuses that were
+/// updated or deleted by each watch event. Runs until the watch fails.
+async fn watch(
+    client: Client,
+    label_selector: &str,
+    mut on_change: impl FnMut(Vec<StatusChange>),
+) -> Result<()> {
+    let api = Api::<TrafficSplit>::all(client);
+    let events = watcher(api, watcher::Config::default().labels(label_selector)).default_backoff();
+    tokio::pin!(events);
+
+    let mut statuses = BTreeMap::<Key, TrafficSplitStatus>::new();
+    while let Some(ev) = events.next().await {
+        let mut changes = Vec::new();
+        match ev.context("failed to watch TrafficSplits")? {
+            watcher::Event::Applied(ts) => {
+                let key = split_key(&ts);
+                changes.extend(apply_change(&mut statuses, key, split_status(ts)));
+            }
+            watcher::Event::Deleted(ts) => {
+                changes.extend(apply_change(&mut statuses, split_key(&ts), None));
+            }
+            watcher::Event::Restarted(tss) => {
+                let mut removed = statuses.keys().cloned().collect::<BTreeSet<_>>();
+                for ts in tss {
+                    let key = split_key(&ts);
+                    removed.remove(&key);
+                    changes.extend(apply_change(&mut statuses, key, split_status(ts)));
+                }
+                for key in removed {
+                    changes.extend(apply_change(&mut statuses, key, None));
+                }
+            }
+        }
+        if !changes.is_empty() {
+            on_change(changes);
+        }
+    }
+
+    Ok(())
+}
+
+/// Records a split's new status, returning a change if it differs from the previous one.
+fn apply_change(
+    statuses: &mut BTreeMap<Key, TrafficSplitStatus>,
+    key: Key,
+    status: Option<TrafficSplitStatus>,
+) -> Option<StatusChange> {
+    match status {
+        Some(status) => {
+            let previous = statuses.insert(key, status.clone());
+            if previous.as_ref() == Some(&status) {
+                return None;
+            }
+            Some(StatusChange {
+                event: ChangeEvent::Updated,
+                status,
+                previous_status: previous.map(|p| p.status),
+            })
+        }
+        None => statuses.remove(&key).map(|status| StatusChange {
+            event: ChangeEvent::Deleted,
+            status,
+            previous_status: None,
+        }),
+    }
+}
+
+fn split_key(ts: &TrafficSplit) -> Key {
+    (
+        ts.namespace().expect("TrafficSplits must be namespaced"),
+        ts.name_any(),
+    )
+}
+
 fn active_backends(ts: &TrafficSplit) -> Vec<String> {
     ts.spec
         .backends
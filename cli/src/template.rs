@@ -0,0 +1,476 @@
+//! Renders the failover Helm chart's templates without Helm.
+//!
+//! Only the subset of the Go template language used by the chart is supported: value and release
+//! lookups, variables, `if`/`end` blocks, whitespace trimming, and the `printf`, `print`,
+//! `include`, `default`, `toYaml`, and `sha256sum` functions. Anything else fails to render rather
+//! than producing incorrect manifests.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::{collections::HashMap, fmt::Write};
+
+/// The chart's templates directory, as exposed by `.Template.BasePath`
+const BASE_PATH: &str = "templates";
+
+pub(crate) struct Release {
+    pub name: String,
+    pub namespace: String,
+}
+
+/// Renders templates from a chart, keyed by their path relative to the chart root (e.g.
+/// `templates/linkerd-failover-rbac.yaml`)
+pub(crate) struct Renderer<'a> {
+    templates: &'a HashMap<String, &'a str>,
+    values: &'a Value,
+    release: &'a Release,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Action(String),
+    If(String, Vec<Node>),
+}
+
+impl<'a> Renderer<'a> {
+    pub(crate) fn new(
+        templates: &'a HashMap<String, &'a str>,
+        values: &'a Value,
+        release: &'a Release,
+    ) -> Self {
+        Self {
+            templates,
+            values,
+            release,
+        }
+    }
+
+    pub(crate) fn render(&self, path: &str) -> Result<String> {
+        let src = self
+            .templates
+            .get(path)
+            .with_context(|| format!("template {path} not found"))?;
+        let nodes = parse(src).with_context(|| format!("failed to parse {path}"))?;
+        let mut vars = HashMap::new();
+        let mut out = String::new();
+        self.render_nodes(&nodes, &mut vars, &mut out)
+            .with_context(|| format!("failed to render {path}"))?;
+        Ok(out)
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        vars: &mut HashMap<String, Value>,
+        out: &mut String,
+    ) -> Result<()> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Action(action) => {
+                    if let Some(value) = self.eval_action(action, vars)? {
+                        out.push_str(&to_text(&value)?);
+                    }
+                }
+                Node::If(cond, body) => {
+                    if truthy(&self.eval_pipeline(cond, vars)?) {
+                        self.render_nodes(body, vars, out)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates an action, returning `None` for variable declarations, which produce no output.
+    fn eval_action(
+        &self,
+        action: &str,
+        vars: &mut HashMap<String, Value>,
+    ) -> Result<Option<Value>> {
+        if let Some((var, pipeline)) = action.split_once(":=") {
+            let var = var.trim();
+            if !var.starts_with('$') {
+                bail!("invalid variable declaration: {action}");
+            }
+            let value = self.eval_pipeline(pipeline, vars)?;
+            vars.insert(var.to_string(), value);
+            return Ok(None);
+        }
+        self.eval_pipeline(action, vars).map(Some)
+    }
+
+    fn eval_pipeline(&self, pipeline: &str, vars: &HashMap<String, Value>) -> Result<Value> {
+        let mut commands = split_outside(pipeline, '|').into_iter();
+        let first = commands.next().context("empty pipeline")?;
+        let mut value = self.eval_command(&tokenize(first)?, None, vars)?;
+        for command in commands {
+            value = self.eval_command(&tokenize(command)?, Some(value), vars)?;
+        }
+        Ok(value)
+    }
+
+    /// Evaluates a command. When the command is part of a pipeline, the previous command's value
+    /// is passed as its final argument.
+    fn eval_command(
+        &self,
+        tokens: &[String],
+        piped: Option<Value>,
+        vars: &HashMap<String, Value>,
+    ) -> Result<Value> {
+        let (name, args) = tokens.split_first().context("empty command")?;
+        let mut args = args
+            .iter()
+            .map(|arg| self.eval_operand(arg, vars))
+            .collect::<Result<Vec<_>>>()?;
+        args.extend(piped);
+
+        match name.as_str() {
+            "default" => match args.as_slice() {
+                [default, value] => Ok(if truthy(value) {
+                    value.clone()
+                } else {
+                    default.clone()
+                }),
+                [default] => Ok(default.clone()),
+                _ => bail!("default expects 1 or 2 arguments"),
+            },
+            "print" => Ok(Value::String(
+                args.iter().map(to_text).collect::<Result<String>>()?,
+            )),
+            "printf" => {
+                let (format, args) = args.split_first().context("printf expects a format")?;
+                printf(&to_text(format)?, args).map(Value::String)
+            }
+            "include" => match args.as_slice() {
+                [Value::String(path), _] => Ok(Value::String(self.render(path)?)),
+                _ => bail!("include expects a template name and a context"),
+            },
+            "toYaml" => match args.as_slice() {
+                [value] => Ok(Value::String(
+                    serde_yaml::to_string(value)?.trim_end().to_string(),
+                )),
+                _ => bail!("toYaml expects 1 argument"),
+            },
+            "sha256sum" => match args.as_slice() {
+                [value] => {
+                    let digest = openssl::sha::sha256(to_text(value)?.as_bytes());
+                    let hex = digest.iter().fold(String::new(), |mut hex, b| {
+                        let _ = write!(hex, "{b:02x}");
+                        hex
+                    });
+                    Ok(Value::String(hex))
+                }
+                _ => bail!("sha256sum expects 1 argument"),
+            },
+            _ if args.is_empty() => self.eval_operand(name, vars),
+            _ => bail!("unsupported function {name}"),
+        }
+    }
+
+    fn eval_operand(&self, operand: &str, vars: &HashMap<String, Value>) -> Result<Value> {
+        if let Some(inner) = operand.strip_prefix('(').and_then(|o| o.strip_suffix(')')) {
+            return self.eval_pipeline(inner, vars);
+        }
+        if let Some(literal) = operand.strip_prefix('"').and_then(|o| o.strip_suffix('"')) {
+            return Ok(Value::String(literal.to_string()));
+        }
+        // The root context is only ever passed through to `include`.
+        if operand == "." || operand == "$" {
+            return Ok(Value::Null);
+        }
+
+        let path = operand.strip_prefix('$').unwrap_or(operand);
+        if let Some(path) = path.strip_prefix(".Values") {
+            return Ok(lookup(self.values, path));
+        }
+        match path {
+            ".Release.Name" => Ok(Value::String(self.release.name.clone())),
+            ".Release.Namespace" => Ok(Value::String(self.release.namespace.clone())),
+            ".Template.BasePath" => Ok(Value::String(BASE_PATH.to_string())),
+            _ if operand.starts_with('$') => vars
+                .get(operand)
+                .cloned()
+                .with_context(|| format!("undefined variable {operand}")),
+            _ => bail!("unsupported operand {operand}"),
+        }
+    }
+}
+
+/// Formats `args` with the `%s`, `%v`, `%d` and `%q` verbs and `%%` escapes. Flags, widths and
+/// other verbs are unsupported.
+fn printf(format: &str, args: &[Value]) -> Result<String> {
+    let mut args = args.iter();
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let verb = chars.next().context("printf format ends with %")?;
+        if verb == '%' {
+            out.push('%');
+            continue;
+        }
+        let arg = args
+            .next()
+            .with_context(|| format!("missing argument for %{verb} in printf"))?;
+        match (verb, arg) {
+            ('s' | 'v', arg) => out.push_str(&to_text(arg)?),
+            ('d', Value::Number(n)) if n.is_i64() || n.is_u64() => out.push_str(&n.to_string()),
+            ('d', arg) => bail!("%d expects an integer, got {arg}"),
+            ('q', arg) => out.push_str(&serde_json::to_string(&to_text(arg)?)?),
+            (verb, _) => bail!("unsupported printf verb %{verb}"),
+        }
+    }
+    if args.next().is_some() {
+        bail!("too many arguments to printf");
+    }
+    Ok(out)
+}
+
+fn parse(src: &str) -> Result<Vec<Node>> {
+    let mut stack: Vec<(Option<String>, Vec<Node>)> = vec![(None, Vec::new())];
+    let mut rest = src;
+    let mut trim_next = false;
+    while !rest.is_empty() {
+        let (text, action) = match rest.find("{{") {
+            Some(start) => {
+                let end = rest[start..]
+                    .find("}}")
+                    .map(|end| start + end)
+                    .context("unterminated action")?;
+                let action = &rest[start + 2..end];
+                let text = &rest[..start];
+                rest = &rest[end + 2..];
+                (text, Some(action))
+            }
+            None => {
+                let text = rest;
+                rest = "";
+                (text, None)
+            }
+        };
+
+        let mut text = if trim_next { text.trim_start() } else { text };
+        trim_next = false;
+        let action = action.map(|action| {
+            let action = match action.strip_prefix('-') {
+                Some(action) => {
+                    text = text.trim_end();
+                    action
+                }
+                None => action,
+            };
+            match action.strip_suffix('-') {
+                Some(action) => {
+                    trim_next = true;
+                    action.trim()
+                }
+                None => action.trim(),
+            }
+        });
+
+        let (_, nodes) = stack.last_mut().expect("stack must not be empty");
+        if !text.is_empty() {
+            nodes.push(Node::Text(text.to_string()));
+        }
+        match action {
+            None => {}
+            Some(action) if action.starts_with("/*") => {}
+            Some("end") => {
+                let (cond, body) = stack.pop().expect("stack must not be empty");
+                let cond = cond.context("unexpected end")?;
+                let (_, nodes) = stack.last_mut().context("unexpected end")?;
+                nodes.push(Node::If(cond, body));
+            }
+            Some(action) => match action.strip_prefix("if ") {
+                Some(cond) => stack.push((Some(cond.to_string()), Vec::new())),
+                None => nodes.push(Node::Action(action.to_string())),
+            },
+        }
+    }
+
+    match stack.pop() {
+        Some((None, nodes)) if stack.is_empty() => Ok(nodes),
+        _ => bail!("unterminated if"),
+    }
+}
+
+/// Splits a command into its function name and arguments, keeping quoted strings and
+/// parenthesized pipelines intact
+fn tokenize(command: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    for c in command.trim().chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            c if c.is_whitespace() && !quoted && depth == 0 => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        token.push(c);
+    }
+    if quoted || depth != 0 {
+        bail!("unbalanced command: {command}");
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Splits `s` on `sep`, ignoring separators within quotes or parentheses
+fn split_outside(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            c if c == sep && !quoted && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn lookup(values: &Value, path: &str) -> Value {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(values, |value, key| value.get(key))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map_or(false, |n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+fn to_text(value: &Value) -> Result<String> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::String(s) => Ok(s.clone()),
+        Value::Bool(_) | Value::Number(_) => Ok(value.to_string()),
+        Value::Array(_) | Value::Object(_) => bail!("cannot print a collection without toYaml"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(src: &str, values: Value) -> String {
+        let templates = Some(("templates/t.yaml".to_string(), src))
+            .into_iter()
+            .collect();
+        let release = Release {
+            name: "linkerd-failover".to_string(),
+            namespace: "linkerd-failover".to_string(),
+        };
+        Renderer::new(&templates, &values, &release)
+            .render("templates/t.yaml")
+            .unwrap()
+    }
+
+    #[test]
+    fn renders_values_and_defaults() {
+        let src = r#"a: {{.Values.image.tag }}
+{{- $sel := printf "controlled-by=%s" .Release.Name}}
+b: {{.Values.selector | default $sel}}
+"#;
+        assert_eq!(
+            render(src, serde_json::json!({"image": {"tag": "0.1.3"}})),
+            "a: 0.1.3\nb: controlled-by=linkerd-failover\n"
+        );
+        assert_eq!(
+            render(
+                src,
+                serde_json::json!({"image": {"tag": "0.1.3"}, "selector": "x=y"})
+            ),
+            "a: 0.1.3\nb: x=y\n"
+        );
+    }
+
+    #[test]
+    fn renders_printf_verbs() {
+        let src = r#"{{ printf "%s=%d %q 100%%" .Values.key .Values.count .Values.key }}"#;
+        assert_eq!(
+            render(src, serde_json::json!({"key": "a", "count": 3})),
+            r#"a=3 "a" 100%"#
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_templates() {
+        let templates = [
+            r#"{{ printf "%5s" .Values.key }}"#,
+            r#"{{ printf "%s %s" .Values.key }}"#,
+            r#"{{ printf "%s" .Values.key .Values.key }}"#,
+            "{{ .Release.Service }}",
+            "{{ .Values.key | quote }}",
+            "{{- range .Values.list }}{{ end }}",
+        ];
+        for src in templates {
+            let templates = Some(("templates/t.yaml".to_string(), src))
+                .into_iter()
+                .collect();
+            let release = Release {
+                name: "linkerd-failover".to_string(),
+                namespace: "linkerd-failover".to_string(),
+            };
+            let values = serde_json::json!({"key": "a", "list": []});
+            assert!(
+                Renderer::new(&templates, &values, &release)
+                    .render("templates/t.yaml")
+                    .is_err(),
+                "{} should fail to render",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let src = r#"kind: ServiceAccount
+{{- if .Values.imagePullSecrets }}
+imagePullSecrets:
+{{ .Values.imagePullSecrets | toYaml }}
+{{- end }}
+"#;
+        assert_eq!(
+            render(src, serde_json::json!({"imagePullSecrets": []})),
+            "kind: ServiceAccount\n"
+        );
+        assert_eq!(
+            render(
+                src,
+                serde_json::json!({"imagePullSecrets": [{"name": "creds"}]})
+            ),
+            "kind: ServiceAccount\nimagePullSecrets:\n- name: creds\n"
+        );
+    }
+}
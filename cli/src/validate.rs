@@ -0,0 +1,230 @@
+//! Detects TrafficSplit misconfigurations that prevent the failover controller from doing its job.
+
+use kube::ResourceExt;
+use linkerd_failover_controller::{
+    health::AllOf,
+    locality::LocalityHealth,
+    multicluster::MulticlusterHealth,
+    prometheus,
+    traffic_split::{self, AllUnavailable, Decision},
+    HealthSource, TrafficSplit,
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Returns the split's primary service if it is not one of the split's backends
+pub(crate) fn primary_not_backend(split: &TrafficSplit) -> Option<&str> {
+    let primary = traffic_split::primary_service(split)?;
+    if split
+        .spec
+        .backends
+        .iter()
+        .any(|b| b.service == primary.service)
+    {
+        return None;
+    }
+    Some(primary.service)
+}
+
+/// Returns the services listed more than once in the split's backends, along with how many times
+/// each is listed
+pub(crate) fn duplicate_backends(split: &TrafficSplit) -> Vec<(&str, usize)> {
+    let mut counts = BTreeMap::<&str, usize>::new();
+    for backend in &split.spec.backends {
+        *counts.entry(&backend.service).or_default() += 1;
+    }
+    counts.into_iter().filter(|(_, n)| *n > 1).collect()
+}
+
+/// Returns true if the split sets thresholds on its backends' Prometheus metrics
+pub(crate) fn fails_over_on_metrics(split: &TrafficSplit) -> bool {
+    let annotations = split.annotations();
+    annotations.contains_key(prometheus::MIN_SUCCESS_RATE_ANNOTATION)
+        || annotations.contains_key(prometheus::MAX_LATENCY_ANNOTATION)
+}
+
+/// Reports backend health as the controller does, from the backends' `Endpoints` and, when the
+/// controller enables them, the health of remote gateways and the backends' distance from the
+/// controller's locality
+pub(crate) fn controller_health(
+    endpoints: impl HealthSource + 'static,
+    multicluster: Option<MulticlusterHealth>,
+    locality: Option<LocalityHealth>,
+) -> AllOf {
+    let mut sources: Vec<Arc<dyn HealthSource>> = vec![Arc::new(endpoints)];
+    if let Some(multicluster) = multicluster {
+        sources.push(Arc::new(multicluster));
+    }
+    if let Some(locality) = locality {
+        sources.push(Arc::new(locality));
+    }
+    AllOf(sources)
+}
+
+/// Returns true if the split's weights differ from those the controller computes for it, given the
+/// members of its failover group, the health of their backends and the controller's default
+/// [`AllUnavailable`] behaviour
+pub(crate) fn weights_outdated(
+    split: &TrafficSplit,
+    members: &[&TrafficSplit],
+    health: &dyn HealthSource,
+    default_policy: AllUnavailable,
+) -> bool {
+    traffic_split::decide_group(split, members, health)
+        .map_or(false, |d| !matches_decision(split, &d, default_policy))
+}
+
+/// Returns true if the split's weights are those computed for it. When all of its backends are
+/// unavailable, the split's annotated [`AllUnavailable`] behaviour applies, or else the
+/// controller's `default_policy`.
+pub(crate) fn matches_decision(
+    split: &TrafficSplit,
+    decision: &Decision,
+    default_policy: AllUnavailable,
+) -> bool {
+    let policy = traffic_split::all_unavailable_policy(split, default_policy);
+    let desired = traffic_split::apply_all_unavailable(split, decision.clone(), policy);
+    split.spec.backends == desired.backends
+}
+
+/// Groups splits by namespace and apex service, returning the groups that contain more than one
+/// split as `(namespace, apex, split names)`
+pub(crate) fn shared_apexes<'a>(
+    splits: impl IntoIterator<Item = &'a TrafficSplit>,
+) -> Vec<(String, String, Vec<String>)> {
+    let mut apexes = BTreeMap::<(String, String), Vec<String>>::new();
+    for split in splits {
+        if split.spec.service.is_empty() {
+            continue;
+        }
+        let namespace = split.namespace().expect("TrafficSplits must be namespaced");
+        apexes
+            .entry((namespace, split.spec.service.clone()))
+            .or_default()
+            .push(split.name_any());
+    }
+    apexes
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|((namespace, apex), names)| (namespace, apex, names))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::core::v1::{EndpointAddress, EndpointSubset, Endpoints, Service};
+    use linkerd_failover_controller::{
+        endpoints,
+        multicluster::{self, Link, LinkSpec},
+        traffic_split::{Backend, TrafficSplitSpec},
+    };
+    use std::collections::HashMap;
+
+    fn endpoints(name: &str, namespace: &str, ready: bool) -> Endpoints {
+        let address = EndpointAddress {
+            ip: "10.0.0.1".to_string(),
+            ..Default::default()
+        };
+        let mut ep = Endpoints::default();
+        ep.metadata.name = Some(name.to_string());
+        ep.metadata.namespace = Some(namespace.to_string());
+        ep.subsets = Some(vec![if ready {
+            EndpointSubset {
+                addresses: Some(vec![address]),
+                ..Default::default()
+            }
+        } else {
+            EndpointSubset {
+                not_ready_addresses: Some(vec![address]),
+                ..Default::default()
+            }
+        }]);
+        ep
+    }
+
+    fn split(weights: [u32; 3]) -> TrafficSplit {
+        let mut ts = TrafficSplit::new(
+            "api",
+            TrafficSplitSpec {
+                service: "api".to_string(),
+                backends: ["api", "api-east", "api-west"]
+                    .iter()
+                    .zip(weights)
+                    .map(|(service, weight)| Backend {
+                        service: service.to_string(),
+                        weight,
+                    })
+                    .collect(),
+            },
+        );
+        ts.metadata.namespace = Some("default".to_string());
+        ts
+    }
+
+    /// A mirror service whose gateway is down is taken out by the controller even though its
+    /// `Endpoints` are ready, so the CLI must reproduce the gateway's health to agree with it.
+    #[test]
+    fn gateway_down_backends_are_unavailable() {
+        let eps = vec![
+            endpoints("api", "default", false),
+            endpoints("api-east", "default", true),
+            endpoints("api-west", "default", true),
+        ]
+        .into_iter()
+        .map(|ep| (ep.metadata.name.clone().unwrap(), ep))
+        .collect::<HashMap<_, _>>();
+        let endpoints_health =
+            move |_: &TrafficSplit, service: &str| endpoints::health(eps.get(service));
+
+        let mut mirror = Service::default();
+        mirror.metadata.name = Some("api-east".to_string());
+        mirror.metadata.namespace = Some("default".to_string());
+        mirror.labels_mut().insert(
+            multicluster::CLUSTER_NAME_LABEL.to_string(),
+            "east".to_string(),
+        );
+        let mut link = Link::new(
+            "east",
+            LinkSpec {
+                target_cluster_name: "east".to_string(),
+                ..Default::default()
+            },
+        );
+        link.metadata.namespace = Some(multicluster::DEFAULT_NAMESPACE.to_string());
+        let gateway = endpoints("probe-gateway-east", multicluster::DEFAULT_NAMESPACE, false);
+        let health = controller_health(
+            endpoints_health.clone(),
+            Some(MulticlusterHealth::from_objects(
+                multicluster::DEFAULT_NAMESPACE,
+                vec![mirror],
+                vec![gateway],
+                vec![link],
+            )),
+            None,
+        );
+
+        let failed_over = split([0, 0, 1]);
+        assert!(!weights_outdated(
+            &failed_over,
+            &[&failed_over],
+            &health,
+            AllUnavailable::default()
+        ));
+        let stale = split([0, 1, 1]);
+        assert!(weights_outdated(
+            &stale,
+            &[&stale],
+            &health,
+            AllUnavailable::default()
+        ));
+
+        // Without the gateway's health, the controller's weights look outdated.
+        let health = controller_health(endpoints_health, None, None);
+        assert!(weights_outdated(
+            &failed_over,
+            &[&failed_over],
+            &health,
+            AllUnavailable::default()
+        ));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::{
+    endpoints,
+    health::AllOf,
+    traffic_split::{self, AllUnavailable, FailoverUpdate},
+    Ctx, Endpoints, HealthSource, TrafficSplit,
+};
+use futures::prelude::*;
+use k8s_openapi::api::core::v1::Service;
+use kube::{
+    runtime::{
+        reflector,
+        watcher::{self, Event},
+        WatchStreamExt,
+    },
+    Api, Client, Resource,
+};
+use kubert::{initialized, runtime::Store, shutdown};
+use serde::de::DeserializeOwned;
+use std::{fmt::Debug, sync::Arc};
+use tokio::{sync::mpsc, time};
+use tracing::Instrument;
+
+/// The label selector used to find traffic splits, unless otherwise configured
+pub const DEFAULT_SELECTOR: &str = "failover.linkerd.io/controlled-by";
+
+/// Observes the controller's updates, e.g. to export metrics or to notify other systems
+pub trait Hook: Send + Sync {
+    /// Called after a traffic split's weights are patched
+    fn patched(&self, update: &FailoverUpdate);
+}
+
+impl<F> Hook for F
+where
+    F: Fn(&FailoverUpdate) + Send + Sync,
+{
+    fn patched(&self, update: &FailoverUpdate) {
+        (self)(update)
+    }
+}
+
+/// Configures a [`FailoverController`]
+pub struct Builder {
+    client: Client,
+    selector: String,
+    field_manager: String,
+    health: Option<Arc<dyn HealthSource>>,
+    health_checks: Vec<Arc<dyn HealthSource>>,
+    hooks: Vec<Arc<dyn Hook>>,
+    all_unavailable: AllUnavailable,
+    resync_interval: Option<time::Duration>,
+    write_timeout: time::Duration,
+    patch_queue_capacity: usize,
+    runtime: Option<RuntimeHandles>,
+}
+
+/// Ties the controller to a kubert runtime's readiness and graceful shutdown
+struct RuntimeHandles {
+    endpoints_initialized: initialized::Handle,
+    traffic_splits_initialized: initialized::Handle,
+    shutdown: shutdown::Watch,
+}
+
+/// Watches traffic splits and endpoints, updating the splits' weights as backends become ready or
+/// not ready
+pub struct FailoverController {
+    config: Builder,
+}
+
+impl FailoverController {
+    pub fn builder(client: Client) -> Builder {
+        Builder {
+            field_manager: traffic_split::default_field_manager(client.default_namespace()),
+            client,
+            selector: DEFAULT_SELECTOR.to_string(),
+            health: None,
+            health_checks: Vec::new(),
+            hooks: Vec::new(),
+            all_unavailable: AllUnavailable::default(),
+            resync_interval: None,
+            write_timeout: time::Duration::from_secs(10),
+            // This should be large enough to handle all traffic splits in the cluster so that a
+            // restart doesn't completely fill the queue; but it shouldn't be so large that slow
+            // writes can cause the process to balloon memory usage.
+            patch_queue_capacity: 1000,
+            runtime: None,
+        }
+    }
+
+    /// Runs the controller. When tied to a runtime with [`Builder::runtime`], the controller stops
+    /// watching and patching traffic splits once the runtime begins to shut down; otherwise it runs
+    /// until the returned future is dropped.
+    pub async fn run(self) {
+        let Builder {
+            client,
+            selector,
+            field_manager,
+            health,
+            health_checks,
+            hooks,
+            all_unavailable,
+            resync_interval,
+            write_timeout,
+            patch_queue_capacity,
+            runtime,
+        } = self.config;
+        let (endpoints_initialized, traffic_splits_initialized, shutdown) = match runtime {
+            Some(rt) => (
+                Some(rt.endpoints_initialized),
+                Some(rt.traffic_splits_initialized),
+                Some(rt.shutdown),
+            ),
+            None => (None, None, None),
+        };
+
+        // Create cached watches for traffic splits and endpoints. This enables us to watch for
+        // updates and to lookup previously-observed objects.
+        let (endpoints, endpoints_events) = cache(
+            Api::<Endpoints>::all(client.clone()),
+            watcher::Config::default(),
+            endpoints_initialized,
+        );
+        let (traffic_splits, traffic_split_events) = cache(
+            Api::<TrafficSplit>::all(client.clone()),
+            watcher::Config::default().labels(&selector),
+            traffic_splits_initialized,
+        );
+
+        // Endpoints changes trigger updates regardless of the health source, so the endpoints
+        // are always watched.
+        let mut health = health.unwrap_or_else(|| Arc::new(endpoints));
+        if !health_checks.is_empty() {
+            health = Arc::new(AllOf(
+                Some(health).into_iter().chain(health_checks).collect(),
+            ));
+        }
+
+        let (patches_tx, patches_rx) = mpsc::channel(patch_queue_capacity);
+        let ctx = Ctx {
+            health,
+            traffic_splits,
+            patches: patches_tx,
+            field_manager: field_manager.clone(),
+            all_unavailable,
+            unavailable: Default::default(),
+        };
+
+        // We process the watches on a single task to avoid cache coherency issues caused by
+        // concurrent updates. For example, when processing a traffic split update, we'll iterate
+        // through its backends and look up the endpoint for each. We don't want the endpoint
+        // states to change while looping--for example, changing the state of the primary backend.
+        // By processing both watches on a single task, we ensure that the cache cannot be updated
+        // while an update is being processed.
+        let watches = async move {
+            let eps = endpoints::process(endpoints_events, ctx.clone())
+                .instrument(tracing::info_span!("endpoints"));
+            let ts = traffic_split::process(traffic_split_events, ctx.clone())
+                .instrument(tracing::info_span!("trafficsplit"));
+            let resync = async {
+                if let Some(interval) = resync_interval {
+                    let mut interval = time::interval(interval);
+                    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+                    // The first tick completes immediately, before the caches are populated.
+                    interval.tick().await;
+                    loop {
+                        interval.tick().await;
+                        traffic_split::resync(&ctx).await;
+                    }
+                }
+            }
+            .instrument(tracing::info_span!("resync"));
+            tokio::join!(eps, ts, resync);
+        };
+
+        // Patches are applied separately from the watches, which helps to prevent conflicting
+        // patches by serializing all updates.
+        let patches =
+            traffic_split::apply_patches(patches_rx, client, &field_manager, write_timeout, &hooks)
+                .instrument(tracing::info_span!("patch"));
+
+        // The watches and patches stop together when the runtime shuts down, so that no update is
+        // sent after the patch queue is closed.
+        let run = async {
+            tokio::join!(watches, patches);
+        };
+        match shutdown {
+            Some(shutdown) => tokio::select! {
+                _ = run => {}
+                _ = shutdown.signaled() => tracing::debug!("shutting down"),
+            },
+            None => run.await,
+        }
+    }
+}
+
+impl Builder {
+    /// Sets the label selector that determines which traffic splits are managed
+    pub fn selector(mut self, selector: impl Into<String>) -> Self {
+        self.selector = selector.into();
+        self
+    }
+
+    /// Sets the field manager used to patch traffic splits. Each controller should use a distinct
+    /// field manager so that competing controllers can be detected.
+    pub fn field_manager(mut self, field_manager: impl Into<String>) -> Self {
+        self.field_manager = field_manager.into();
+        self
+    }
+
+    /// Replaces the default health source, which reports backends as ready when their
+    /// `Endpoints` have ready addresses
+    pub fn health_source(mut self, health: impl HealthSource + 'static) -> Self {
+        self.health = Some(Arc::new(health));
+        self
+    }
+
+    /// Adds a health source that must also report a backend as ready for it to receive traffic
+    pub fn health_check(mut self, health: impl HealthSource + 'static) -> Self {
+        self.health_checks.push(Arc::new(health));
+        self
+    }
+
+    /// Sets how traffic splits are weighted while their primary and all of their fallbacks are
+    /// unavailable, unless they are annotated otherwise
+    pub fn all_unavailable(mut self, policy: AllUnavailable) -> Self {
+        self.all_unavailable = policy;
+        self
+    }
+
+    /// Re-evaluates all traffic splits periodically. This is necessary for health sources whose
+    /// state changes independently of the watched resources.
+    pub fn resync_interval(mut self, interval: time::Duration) -> Self {
+        self.resync_interval = Some(interval);
+        self
+    }
+
+    /// Adds a hook that is notified of each traffic split update
+    pub fn hook(mut self, hook: impl Hook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Sets how long to wait for a traffic split patch to complete
+    pub fn write_timeout(mut self, timeout: time::Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Sets how many pending traffic split patches may be queued
+    pub fn patch_queue_capacity(mut self, capacity: usize) -> Self {
+        self.patch_queue_capacity = capacity;
+        self
+    }
+
+    /// Ties the controller to the runtime: the runtime is not ready until the controller's caches
+    /// of endpoints and traffic splits are populated, and patches stop when the runtime shuts down.
+    pub fn runtime<S>(mut self, runtime: &mut kubert::Runtime<S>) -> Self {
+        self.runtime = Some(RuntimeHandles {
+            endpoints_initialized: runtime.initialized_handle(),
+            traffic_splits_initialized: runtime.initialized_handle(),
+            shutdown: runtime.shutdown_handle(),
+        });
+        self
+    }
+
+    pub fn build(self) -> FailoverController {
+        FailoverController { config: self }
+    }
+}
+
+/// Watches all services, caching them in a store that health sources may share, e.g. a
+/// [`Prober`](crate::probe::Prober) and a [`LocalityHealth`](crate::locality::LocalityHealth). The
+/// returned future must be spawned for the store to be populated.
+pub fn watch_services(client: Client) -> (Store<Service>, impl Future<Output = ()>) {
+    let (services, events) = cache(
+        Api::<Service>::all(client),
+        watcher::Config::default(),
+        None,
+    );
+    (services, events.for_each(|_| future::ready(())))
+}
+
+/// Watches the resources selected by `config`, caching them in a store. Watch errors are logged
+/// and the watch is retried with a backoff. The `initialized` handle, if any, is released once the
+/// store is first populated.
+pub(crate) fn cache<K>(
+    api: Api<K>,
+    config: watcher::Config,
+    initialized: Option<initialized::Handle>,
+) -> (reflector::Store<K>, impl Stream<Item = Event<K>>)
+where
+    K: Resource + Clone + Debug + DeserializeOwned + Send + Sync + 'static,
+    K::DynamicType: Clone + Default + Eq + std::hash::Hash,
+{
+    let (store, writer) = reflector::store();
+    let events = reflector(writer, watcher::watcher(api, config))
+        .default_backoff()
+        .filter_map(|res| async move {
+            match res {
+                Ok(ev) => Some(ev),
+                Err(error) => {
+                    tracing::info!(%error, "watch failed");
+                    None
+                }
+            }
+        })
+        .inspect({
+            let mut initialized = initialized;
+            move |_| drop(initialized.take())
+        });
+    (store, events)
+}
@@ -1,4 +1,4 @@
-use super::{traffic_split, Ctx};
+use super::{health::Health, traffic_split, Ctx};
 use futures::prelude::*;
 use kube::{
     runtime::{reflector::ObjectRef, watcher::Event},
@@ -17,23 +17,31 @@ where
     }
 }
 
-pub(super) async fn handle(ev: Event<Endpoints>, ctx: &Ctx) {
+/// Handles a single endpoints watch event. Exposed so that the controller's decisions can be
+/// replayed against in-memory stores.
+pub async fn handle(ev: Event<Endpoints>, ctx: &Ctx) {
     match ev {
         Event::Applied(ep) | Event::Deleted(ep) => {
-            let mut updated = 0;
             let ep_name = ep.name_any();
-            for ts in ctx.traffic_splits.state() {
-                if ts.namespace() == ep.namespace()
-                    && ts.spec.backends.iter().any(|b| b.service == ep_name)
-                {
+            let targets = ctx
+                .traffic_splits
+                .state()
+                .iter()
+                .filter(|ts| {
+                    ts.namespace() == ep.namespace()
+                        && ts.spec.backends.iter().any(|b| b.service == ep_name)
+                })
+                .map(|ts| {
                     tracing::debug!(
                         service = %ep_name,
+                        trafficsplit = %ts.name_any(),
                         "updating traffic split for endpoints",
                     );
-                    traffic_split::update(ObjectRef::from_obj(&*ts), ctx).await;
-                    updated += 1;
-                }
-            }
+                    ObjectRef::from_obj(&**ts)
+                })
+                .collect::<Vec<_>>();
+            let updated = targets.len();
+            traffic_split::update_each(targets, ctx).await;
             tracing::debug!(
                 namespace = %ep.namespace().unwrap(),
                 service = %ep_name,
@@ -45,9 +53,57 @@ pub(super) async fn handle(ev: Event<Endpoints>, ctx: &Ctx) {
         Event::Restarted(_) => {
             tracing::debug!("updating traffic splits on endpoints restart");
             // On restart, reconcile all known traffic splits.
-            for ts in ctx.traffic_splits.state() {
-                traffic_split::update(ObjectRef::from_obj(&*ts), ctx).await;
-            }
+            traffic_split::update_all(ctx).await;
         }
     }
 }
+
+/// Returns true if the `Endpoints` resource has ready addresses
+pub fn is_ready(ep: &Endpoints) -> bool {
+    if let Some(subsets) = &ep.subsets {
+        return subsets.iter().any(|s| match &s.addresses {
+            Some(addrs) => !addrs.is_empty(),
+            None => false,
+        });
+    }
+
+    false
+}
+
+/// Reports a backend as healthy if its `Endpoints` resource exists and has ready addresses. The
+/// backend's capacity is its number of ready addresses.
+pub fn health(ep: Option<&Endpoints>) -> Health {
+    let ep = match ep {
+        Some(ep) => ep,
+        None => return Health::not_ready("no endpoints"),
+    };
+    let ready = ready_addresses(ep);
+    let not_ready = not_ready_addresses(ep);
+    let detail = format!("{ready} ready, {not_ready} not ready addresses");
+    let health = if is_ready(ep) {
+        Health::ready(detail)
+    } else {
+        Health::not_ready(detail)
+    };
+    let count = |n: usize| n.min(u32::MAX as usize) as u32;
+    health
+        .with_capacity(count(ready))
+        .with_addresses(count(ready + not_ready))
+}
+
+/// Returns the number of ready addresses in the `Endpoints` resource
+pub fn ready_addresses(ep: &Endpoints) -> usize {
+    ep.subsets.iter().flatten().fold(0, |n, s| {
+        n + s.addresses.as_ref().map_or(0, |addrs| addrs.len())
+    })
+}
+
+/// Returns the number of addresses in the `Endpoints` resource that are not ready
+pub fn not_ready_addresses(ep: &Endpoints) -> usize {
+    ep.subsets.iter().flatten().fold(0, |n, s| {
+        n + s
+            .not_ready_addresses
+            .as_ref()
+            .map_or(0, |addrs| addrs.len())
+    })
+}
@@ -0,0 +1,137 @@
+use crate::{
+    endpoints::{self, Endpoints},
+    TrafficSplit,
+};
+use kube::{runtime::reflector::ObjectRef, ResourceExt};
+use kubert::runtime::Store;
+use std::sync::Arc;
+
+/// The health of a backend service, as reported by a [`HealthSource`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Health {
+    /// Whether the backend may receive traffic
+    pub ready: bool,
+    /// Explains the backend's health, e.g. how many of its endpoints are ready
+    pub detail: String,
+    /// How far the backend is from the controller's cluster, if known. Nearer fallbacks are
+    /// preferred by splits that opt in to locality-aware failover.
+    pub distance: Option<u32>,
+    /// How many ready addresses serve the backend, if known. Splits with capacity-proportional
+    /// fallback weights weigh fallbacks by their capacity.
+    pub capacity: Option<u32>,
+    /// How many addresses serve the backend, ready or not, if known. Splits that fail over
+    /// partially compare a degraded primary's capacity to its addresses.
+    pub addresses: Option<u32>,
+    /// Whether traffic must be moved off of the backend, even when an override or the split's
+    /// all-unavailable policy would otherwise send it traffic
+    pub evacuated: bool,
+}
+
+/// Reports the health of backend services to the failover decision.
+///
+/// Sources are queried while a traffic split update is processed, so they should answer from
+/// cached state rather than blocking.
+pub trait HealthSource: Send + Sync {
+    /// Reports the health of one of the split's backends. The split is provided so that sources
+    /// may be configured per split, e.g. through annotations.
+    fn health(&self, split: &TrafficSplit, service: &str) -> Health;
+}
+
+impl Health {
+    pub fn ready(detail: impl Into<String>) -> Self {
+        Self {
+            ready: true,
+            detail: detail.into(),
+            distance: None,
+            capacity: None,
+            addresses: None,
+            evacuated: false,
+        }
+    }
+
+    pub fn not_ready(detail: impl Into<String>) -> Self {
+        Self {
+            ready: false,
+            detail: detail.into(),
+            distance: None,
+            capacity: None,
+            addresses: None,
+            evacuated: false,
+        }
+    }
+
+    pub fn evacuated(detail: impl Into<String>) -> Self {
+        Self {
+            evacuated: true,
+            ..Self::not_ready(detail)
+        }
+    }
+
+    pub fn with_distance(mut self, distance: u32) -> Self {
+        self.distance = Some(distance);
+        self
+    }
+
+    pub fn with_capacity(mut self, capacity: u32) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    pub fn with_addresses(mut self, addresses: u32) -> Self {
+        self.addresses = Some(addresses);
+        self
+    }
+}
+
+/// Allows closures, e.g. over static state, to be used as health sources
+impl<F> HealthSource for F
+where
+    F: Fn(&TrafficSplit, &str) -> Health + Send + Sync,
+{
+    fn health(&self, split: &TrafficSplit, service: &str) -> Health {
+        (self)(split, service)
+    }
+}
+
+/// Reports a backend as ready only if all of the sources report it as ready, and as evacuated if
+/// any of them do. The backend's distance, capacity and addresses are the first ones reported.
+pub struct AllOf(pub Vec<Arc<dyn HealthSource>>);
+
+impl HealthSource for AllOf {
+    fn health(&self, split: &TrafficSplit, service: &str) -> Health {
+        let mut ready = true;
+        let mut details = Vec::new();
+        let mut distance = None;
+        let mut capacity = None;
+        let mut addresses = None;
+        let mut evacuated = false;
+        for source in &self.0 {
+            let health = source.health(split, service);
+            ready &= health.ready;
+            if !health.detail.is_empty() {
+                details.push(health.detail);
+            }
+            distance = distance.or(health.distance);
+            capacity = capacity.or(health.capacity);
+            addresses = addresses.or(health.addresses);
+            evacuated |= health.evacuated;
+        }
+        Health {
+            ready,
+            detail: details.join("; "),
+            distance,
+            capacity,
+            addresses,
+            evacuated,
+        }
+    }
+}
+
+/// Backends are healthy if their cached `Endpoints` resource has ready addresses
+impl HealthSource for Store<Endpoints> {
+    fn health(&self, split: &TrafficSplit, service: &str) -> Health {
+        let namespace = split.namespace().unwrap_or_default();
+        let ep = self.get(&ObjectRef::new(service).within(&namespace));
+        endpoints::health(ep.as_deref())
+    }
+}
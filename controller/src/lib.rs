@@ -3,50 +3,62 @@
 
 use kube::runtime::reflector::ObjectRef;
 use kubert::runtime::Store;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
 use tokio::sync::mpsc;
 
+mod controller;
 pub mod endpoints;
+pub mod health;
+pub mod locality;
+pub mod multicluster;
+pub mod probe;
+pub mod prometheus;
 pub mod traffic_split;
 
-pub use self::{endpoints::Endpoints, traffic_split::TrafficSplit};
+pub use self::{
+    controller::{watch_services, Builder, FailoverController, Hook, DEFAULT_SELECTOR},
+    endpoints::Endpoints,
+    health::{Health, HealthSource},
+    traffic_split::TrafficSplit,
+};
 
 /// Shares state between the endpoints and trafficsplit watches
 #[derive(Clone)]
 pub struct Ctx {
-    pub endpoints: Store<Endpoints>,
+    /// Reports whether each backend may receive traffic, e.g. from the cached `Endpoints`
+    pub health: Arc<dyn HealthSource>,
     pub traffic_splits: Store<TrafficSplit>,
     pub patches: mpsc::Sender<traffic_split::FailoverUpdate>,
-}
-
-impl Ctx {
-    /// Returns true if there is a cached `Endpoints` resource with the given namespace and name and
-    /// it has ready addresses
-    fn endpoints_ready(&self, ns: &str, name: &str) -> bool {
-        if let Some(ep) = self.endpoints.get(&ObjectRef::new(name).within(ns)) {
-            if let Some(subsets) = &ep.subsets {
-                return subsets.iter().any(|s| match &s.addresses {
-                    Some(addrs) => !addrs.is_empty(),
-                    None => false,
-                });
-            }
-        }
-
-        false
-    }
+    /// The field manager this controller patches traffic splits with
+    pub field_manager: String,
+    /// How splits are weighted while all of their backends are unavailable, unless they are
+    /// annotated otherwise
+    pub all_unavailable: traffic_split::AllUnavailable,
+    /// The splits whose backends were all unavailable when they were last evaluated
+    pub unavailable: Arc<Mutex<HashSet<ObjectRef<TrafficSplit>>>>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use k8s_openapi::api::core::v1::{EndpointAddress, EndpointSubset};
-    use kube::runtime::{
-        reflector::{store::Writer, ObjectRef},
-        watcher::Event,
+    use k8s_openapi::{
+        api::core::v1::{EndpointAddress, EndpointSubset},
+        apimachinery::pkg::apis::meta::v1::{FieldsV1, ManagedFieldsEntry},
+    };
+    use kube::{
+        runtime::{
+            reflector::{store::Writer, ObjectRef},
+            watcher::Event,
+        },
+        ResourceExt,
     };
     use tokio_stream::wrappers::ReceiverStream;
     use tokio_test::{assert_pending, assert_ready_eq, task};
 
-    fn init_tracing() -> tracing::subscriber::DefaultGuard {
+    pub(crate) fn init_tracing() -> tracing::subscriber::DefaultGuard {
         tracing::subscriber::set_default(
             tracing_subscriber::fmt()
                 .with_test_writer()
@@ -67,9 +79,12 @@ mod tests {
         let traffic_splits = Writer::default();
         let (tx, patches) = mpsc::channel(capacity);
         let ctx = Ctx {
-            endpoints: endpoints.as_reader(),
+            health: Arc::new(endpoints.as_reader()),
             traffic_splits: traffic_splits.as_reader(),
             patches: tx,
+            field_manager: traffic_split::default_field_manager("linkerd-failover"),
+            all_unavailable: Default::default(),
+            unavailable: Default::default(),
         };
         (
             ctx,
@@ -79,7 +94,7 @@ mod tests {
         )
     }
 
-    fn endpoints_ready(name: impl Into<String>, ip: impl Into<String>) -> Endpoints {
+    pub(crate) fn endpoints_ready(name: impl Into<String>, ip: impl Into<String>) -> Endpoints {
         Endpoints {
             metadata: kube::core::ObjectMeta {
                 name: Some(name.into()),
@@ -96,7 +111,7 @@ mod tests {
         }
     }
 
-    fn endpoints_not_ready(name: impl Into<String>, ip: impl Into<String>) -> Endpoints {
+    pub(crate) fn endpoints_not_ready(name: impl Into<String>, ip: impl Into<String>) -> Endpoints {
         Endpoints {
             metadata: kube::core::ObjectMeta {
                 name: Some(name.into()),
@@ -113,7 +128,7 @@ mod tests {
         }
     }
 
-    fn traffic_split(
+    pub(crate) fn traffic_split(
         name: impl Into<String>,
         primary: impl Into<String>,
         backends: Vec<traffic_split::Backend>,
@@ -132,11 +147,22 @@ mod tests {
                 ),
                 ..Default::default()
             },
-            spec: traffic_split::TrafficSplitSpec { backends },
+            spec: traffic_split::TrafficSplitSpec {
+                service: "apex".to_owned(),
+                backends,
+            },
         }
     }
 
-    fn backend(service: impl Into<String>, weight: u32) -> traffic_split::Backend {
+    fn with_override(mut ts: TrafficSplit, service: impl Into<String>) -> TrafficSplit {
+        ts.annotations_mut().insert(
+            traffic_split::OVERRIDE_ANNOTATION.to_owned(),
+            service.into(),
+        );
+        ts
+    }
+
+    pub(crate) fn backend(service: impl Into<String>, weight: u32) -> traffic_split::Backend {
         traffic_split::Backend {
             service: service.into(),
             weight,
@@ -180,7 +206,9 @@ mod tests {
                     backend("primary", 1),
                     backend("secondary", 0),
                     backend("tertiary", 0),
-                ]
+                ],
+                competing_managers: vec![],
+                all_unavailable: None,
             })
         );
     }
@@ -222,7 +250,77 @@ mod tests {
                     backend("primary", 0),
                     backend("secondary", 1),
                     backend("tertiary", 1),
-                ]
+                ],
+                competing_managers: vec![],
+                all_unavailable: None,
+            })
+        );
+    }
+
+    /// When the primary and all fallbacks become unavailable, the split is weighted according to
+    /// its all-unavailable annotation and a warning is issued once, even if the weights are kept.
+    #[tokio::test]
+    async fn warns_once_when_all_backends_unavailable() {
+        let _log = init_tracing();
+        let (ctx, mut endpoints, mut trafficsplit, mut patches) = mk_ctx(10);
+
+        let restart_eps = Event::Restarted(vec![
+            endpoints_ready("primary", "10.11.12.13"),
+            endpoints_ready("secondary", "10.11.12.14"),
+        ]);
+        endpoints.apply_watcher_event(&restart_eps);
+        endpoints::handle(restart_eps, &ctx).await;
+
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![backend("primary", 1), backend("secondary", 0)],
+        );
+        ts.annotations_mut().insert(
+            traffic_split::ALL_UNAVAILABLE_ANNOTATION.to_owned(),
+            "keep".to_owned(),
+        );
+        let restart_ts = Event::Restarted(vec![ts.clone()]);
+        trafficsplit.apply_watcher_event(&restart_ts);
+        traffic_split::handle(restart_ts, &ctx).await;
+        assert_pending!(patches.poll_next());
+
+        let restart_eps = Event::Restarted(vec![
+            endpoints_not_ready("primary", "10.11.12.13"),
+            endpoints_not_ready("secondary", "10.11.12.14"),
+        ]);
+        endpoints.apply_watcher_event(&restart_eps);
+        endpoints::handle(restart_eps, &ctx).await;
+        assert_ready_eq!(
+            patches.poll_next(),
+            Some(traffic_split::FailoverUpdate {
+                primary_active: true,
+                target: ObjectRef::new("ts0").within("default"),
+                backends: vec![backend("primary", 1), backend("secondary", 0)],
+                competing_managers: vec![],
+                all_unavailable: Some(traffic_split::AllUnavailable::Keep),
+            })
+        );
+
+        // Later evaluations don't repeat the warning.
+        traffic_split::handle(Event::Applied(ts.clone()), &ctx).await;
+        assert_pending!(patches.poll_next());
+
+        // The controller's default removes all traffic.
+        ts.annotations_mut()
+            .remove(traffic_split::ALL_UNAVAILABLE_ANNOTATION);
+        traffic_split::handle(Event::Deleted(ts.clone()), &ctx).await;
+        let ev = Event::Applied(ts);
+        trafficsplit.apply_watcher_event(&ev);
+        traffic_split::handle(ev, &ctx).await;
+        assert_ready_eq!(
+            patches.poll_next(),
+            Some(traffic_split::FailoverUpdate {
+                primary_active: false,
+                target: ObjectRef::new("ts0").within("default"),
+                backends: vec![backend("primary", 0), backend("secondary", 0)],
+                competing_managers: vec![],
+                all_unavailable: Some(traffic_split::AllUnavailable::Zero),
             })
         );
     }
@@ -256,4 +354,474 @@ mod tests {
 
         assert_pending!(patches.poll_next());
     }
+
+    /// Given a traffic split whose override moves traffic off of a ready primary, the split is
+    /// updated to use only the ready fallbacks.
+    #[tokio::test]
+    async fn override_fails_over_to_ready_fallbacks() {
+        let _log = init_tracing();
+        let (ctx, mut endpoints, mut trafficsplit, mut patches) = mk_ctx(10);
+
+        let restart_eps = Event::Restarted(vec![
+            endpoints_ready("primary", "10.11.12.13"),
+            endpoints_not_ready("secondary", "10.11.12.14"),
+            endpoints_ready("tertiary", "10.11.12.15"),
+        ]);
+        endpoints.apply_watcher_event(&restart_eps);
+        endpoints::handle(restart_eps, &ctx).await;
+        assert_pending!(patches.poll_next());
+
+        let restart_ts = Event::Restarted(vec![with_override(
+            traffic_split(
+                "ts0",
+                "primary",
+                vec![
+                    backend("primary", 1),
+                    backend("secondary", 0),
+                    backend("tertiary", 0),
+                ],
+            ),
+            traffic_split::OVERRIDE_FALLBACKS,
+        )]);
+        trafficsplit.apply_watcher_event(&restart_ts);
+        traffic_split::handle(restart_ts, &ctx).await;
+
+        assert_ready_eq!(
+            patches.poll_next(),
+            Some(traffic_split::FailoverUpdate {
+                primary_active: false,
+                target: ObjectRef::new("ts0").within("default"),
+                backends: vec![
+                    backend("primary", 0),
+                    backend("secondary", 0),
+                    backend("tertiary", 1),
+                ],
+                competing_managers: vec![],
+                all_unavailable: None,
+            })
+        );
+    }
+
+    /// Given a traffic split whose override names a backend, all traffic is sent to that backend
+    /// even though the primary is ready.
+    #[tokio::test]
+    async fn override_pins_backend() {
+        let _log = init_tracing();
+        let (ctx, mut endpoints, mut trafficsplit, mut patches) = mk_ctx(10);
+
+        let restart_eps = Event::Restarted(vec![
+            endpoints_ready("primary", "10.11.12.13"),
+            endpoints_ready("secondary", "10.11.12.14"),
+            endpoints_ready("tertiary", "10.11.12.15"),
+        ]);
+        endpoints.apply_watcher_event(&restart_eps);
+        endpoints::handle(restart_eps, &ctx).await;
+        assert_pending!(patches.poll_next());
+
+        let restart_ts = Event::Restarted(vec![with_override(
+            traffic_split(
+                "ts0",
+                "primary",
+                vec![
+                    backend("primary", 1),
+                    backend("secondary", 0),
+                    backend("tertiary", 0),
+                ],
+            ),
+            "secondary",
+        )]);
+        trafficsplit.apply_watcher_event(&restart_ts);
+        traffic_split::handle(restart_ts, &ctx).await;
+
+        assert_ready_eq!(
+            patches.poll_next(),
+            Some(traffic_split::FailoverUpdate {
+                primary_active: false,
+                target: ObjectRef::new("ts0").within("default"),
+                backends: vec![
+                    backend("primary", 0),
+                    backend("secondary", 1),
+                    backend("tertiary", 0),
+                ],
+                competing_managers: vec![],
+                all_unavailable: None,
+            })
+        );
+    }
+
+    /// Given a traffic split without a primary-service annotation, its first backend is treated as
+    /// the primary.
+    #[test]
+    fn primary_defaults_to_first_backend() {
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![backend("secondary", 1), backend("primary", 0)],
+        );
+        assert_eq!(
+            traffic_split::primary_service(&ts),
+            Some(traffic_split::Primary {
+                service: "primary",
+                source: traffic_split::PrimarySource::Annotation,
+            })
+        );
+
+        ts.annotations_mut()
+            .remove(traffic_split::PRIMARY_SERVICE_ANNOTATION);
+        assert_eq!(
+            traffic_split::primary_service(&ts),
+            Some(traffic_split::Primary {
+                service: "secondary",
+                source: traffic_split::PrimarySource::FirstBackend,
+            })
+        );
+    }
+
+    /// The decision depends only on the health reported for each backend, so it can be computed
+    /// without reflectors.
+    #[test]
+    fn decides_from_health_source() {
+        let ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![
+                backend("primary", 1),
+                backend("secondary", 0),
+                backend("tertiary", 0),
+            ],
+        );
+        let health = |_: &TrafficSplit, service: &str| match service {
+            "secondary" => Health::ready("probe succeeded"),
+            _ => Health::not_ready("probe failed"),
+        };
+
+        assert_eq!(
+            traffic_split::decide(&ts, &health),
+            Some(traffic_split::Decision {
+                primary_active: false,
+                backends: vec![
+                    backend("primary", 0),
+                    backend("secondary", 1),
+                    backend("tertiary", 0),
+                ],
+                health: vec![
+                    Health::not_ready("probe failed"),
+                    Health::ready("probe succeeded"),
+                    Health::not_ready("probe failed"),
+                ],
+                all_unavailable: false,
+            })
+        );
+    }
+
+    /// When any member of a failover group leaves its primary, every member moves to the first
+    /// fallback tier that is ready in all of them.
+    #[test]
+    fn groups_fail_over_together() {
+        let mut api = traffic_split(
+            "api",
+            "api",
+            vec![
+                backend("api", 1),
+                backend("api-east", 0),
+                backend("api-west", 0),
+            ],
+        );
+        let mut cache = traffic_split(
+            "cache",
+            "cache",
+            vec![
+                backend("cache", 1),
+                backend("cache-east", 0),
+                backend("cache-west", 0),
+            ],
+        );
+        let other = traffic_split("other", "other", vec![backend("other", 1)]);
+        for ts in [&mut api, &mut cache] {
+            ts.annotations_mut().insert(
+                traffic_split::GROUP_ANNOTATION.to_owned(),
+                "frontend".to_owned(),
+            );
+        }
+        let splits = [api.clone(), cache.clone(), other.clone()];
+        let members = traffic_split::group_members(&api, &splits);
+        assert_eq!(members.len(), 2);
+        assert_eq!(traffic_split::group_members(&other, &splits).len(), 1);
+
+        let weights = |ts: &TrafficSplit, health: &dyn HealthSource| {
+            traffic_split::decide_group(ts, &members, health)
+                .unwrap()
+                .backends
+                .iter()
+                .map(|b| b.weight)
+                .collect::<Vec<_>>()
+        };
+
+        // The cache's primary is down, and the east tier is not ready for the API, so both move
+        // to the west tier even though the API's primary is ready.
+        let health = |_: &TrafficSplit, service: &str| match service {
+            "cache" | "api-east" => Health::not_ready(""),
+            _ => Health::ready(""),
+        };
+        assert_eq!(weights(&api, &health), vec![0, 0, 1]);
+        assert_eq!(weights(&cache, &health), vec![0, 0, 1]);
+
+        // Once all primaries are ready, the group fails back.
+        let health = |_: &TrafficSplit, _: &str| Health::ready("");
+        assert_eq!(weights(&api, &health), vec![1, 0, 0]);
+        assert_eq!(weights(&cache, &health), vec![1, 0, 0]);
+
+        // Without a common ready tier, members are decided individually.
+        let health = |_: &TrafficSplit, service: &str| match service {
+            "cache" | "api-east" | "cache-west" => Health::not_ready(""),
+            _ => Health::ready(""),
+        };
+        assert_eq!(weights(&api, &health), vec![1, 0, 0]);
+        assert_eq!(weights(&cache, &health), vec![0, 1, 0]);
+    }
+
+    /// Splits with capacity-proportional fallback weights weigh each ready fallback by its number
+    /// of ready addresses.
+    #[test]
+    fn weighs_fallbacks_by_capacity() {
+        let endpoints = |name: &str, ready: usize| {
+            let mut ep = endpoints_not_ready(name, "10.0.0.1");
+            let subsets = ep.subsets.as_mut().unwrap();
+            subsets[0].addresses = Some(
+                (0..ready)
+                    .map(|i| EndpointAddress {
+                        ip: format!("10.1.0.{i}"),
+                        ..Default::default()
+                    })
+                    .collect(),
+            );
+            ep
+        };
+        let weights = |ts: &TrafficSplit, ready: &'static [usize]| {
+            let health = move |ts: &TrafficSplit, service: &str| {
+                let i = ts
+                    .spec
+                    .backends
+                    .iter()
+                    .position(|b| b.service == service)
+                    .unwrap();
+                endpoints::health(Some(&endpoints(service, ready[i])))
+            };
+            traffic_split::decide(ts, &health)
+                .unwrap()
+                .backends
+                .iter()
+                .map(|b| b.weight)
+                .collect::<Vec<_>>()
+        };
+
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![
+                backend("primary", 1),
+                backend("secondary", 0),
+                backend("tertiary", 0),
+            ],
+        );
+        ts.annotations_mut().insert(
+            traffic_split::FALLBACK_WEIGHTS_ANNOTATION.to_owned(),
+            traffic_split::CAPACITY.to_owned(),
+        );
+
+        // The primary keeps a weight of 1 while it's ready.
+        assert_eq!(weights(&ts, &[2, 3, 1]), vec![1, 0, 0]);
+        assert_eq!(weights(&ts, &[0, 3, 1]), vec![0, 3, 1]);
+        assert_eq!(weights(&ts, &[0, 3, 0]), vec![0, 3, 0]);
+
+        let mut ts = with_override(ts, traffic_split::OVERRIDE_FALLBACKS);
+        assert_eq!(weights(&ts, &[2, 4, 1]), vec![0, 4, 1]);
+
+        // Other splits weigh all ready fallbacks equally.
+        ts.annotations_mut().clear();
+        assert_eq!(weights(&ts, &[0, 3, 1]), vec![0, 1, 1]);
+    }
+
+    /// Splits that fail over partially shift a share of traffic off of a degraded primary in
+    /// proportion to its addresses that are not ready.
+    #[test]
+    fn fails_over_partially_while_primary_is_degraded() {
+        // Each backend's ready and total addresses
+        let weights = |ts: &TrafficSplit, addresses: &'static [(u32, u32)]| {
+            let health = move |ts: &TrafficSplit, service: &str| {
+                let i = ts
+                    .spec
+                    .backends
+                    .iter()
+                    .position(|b| b.service == service)
+                    .unwrap();
+                let (ready, total) = addresses[i];
+                let health = if ready > 0 {
+                    Health::ready("")
+                } else {
+                    Health::not_ready("")
+                };
+                health.with_capacity(ready).with_addresses(total)
+            };
+            traffic_split::decide(ts, &health)
+                .unwrap()
+                .backends
+                .iter()
+                .map(|b| b.weight)
+                .collect::<Vec<_>>()
+        };
+
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![
+                backend("primary", 1),
+                backend("secondary", 0),
+                backend("tertiary", 0),
+            ],
+        );
+        ts.annotations_mut().insert(
+            traffic_split::FAILOVER_MODE_ANNOTATION.to_owned(),
+            traffic_split::PARTIAL.to_owned(),
+        );
+
+        assert_eq!(weights(&ts, &[(4, 4), (2, 2), (2, 2)]), vec![1, 0, 0]);
+        assert_eq!(weights(&ts, &[(2, 4), (2, 2), (2, 2)]), vec![50, 25, 25]);
+        assert_eq!(weights(&ts, &[(1, 3), (2, 2), (2, 2)]), vec![33, 34, 33]);
+        assert_eq!(weights(&ts, &[(1, 4), (2, 2), (0, 2)]), vec![25, 75, 0]);
+        // Without ready fallbacks, a degraded primary keeps all traffic.
+        assert_eq!(weights(&ts, &[(1, 4), (0, 2), (0, 2)]), vec![1, 0, 0]);
+        assert_eq!(weights(&ts, &[(0, 4), (2, 2), (2, 2)]), vec![0, 1, 1]);
+
+        // The remainder may be spread by capacity.
+        ts.annotations_mut().insert(
+            traffic_split::FALLBACK_WEIGHTS_ANNOTATION.to_owned(),
+            traffic_split::CAPACITY.to_owned(),
+        );
+        assert_eq!(weights(&ts, &[(2, 4), (3, 3), (1, 1)]), vec![50, 38, 12]);
+
+        // Other splits keep all traffic on a ready primary.
+        ts.annotations_mut()
+            .remove(traffic_split::FAILOVER_MODE_ANNOTATION);
+        assert_eq!(weights(&ts, &[(1, 4), (2, 2), (2, 2)]), vec![1, 0, 0]);
+    }
+
+    /// A split without backends has no primary, so there is nothing to decide.
+    #[test]
+    fn no_decision_without_backends() {
+        let mut ts = traffic_split("ts0", "primary", vec![]);
+        ts.annotations_mut()
+            .remove(traffic_split::PRIMARY_SERVICE_ANNOTATION);
+        let health = |_: &TrafficSplit, _: &str| Health::ready("");
+        assert_eq!(traffic_split::decide(&ts, &health), None);
+    }
+
+    /// Given a split whose override pins traffic to an evacuated backend, the override is ignored
+    /// and traffic is routed by readiness instead.
+    #[test]
+    fn evacuation_overrides_pinned_backends() {
+        let health = |_: &TrafficSplit, service: &str| match service {
+            "secondary-east" => Health::evacuated("cluster east is evacuated"),
+            _ => Health::ready(""),
+        };
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![backend("primary", 0), backend("secondary-east", 1)],
+        );
+        ts.annotations_mut().insert(
+            traffic_split::OVERRIDE_ANNOTATION.to_owned(),
+            "secondary-east".to_owned(),
+        );
+
+        let decision = traffic_split::decide(&ts, &health).unwrap();
+        assert_eq!(
+            decision.backends,
+            vec![backend("primary", 1), backend("secondary-east", 0)]
+        );
+        assert!(decision.primary_active);
+    }
+
+    /// Given a split whose backends are all unavailable, evacuated backends get no traffic under
+    /// any all-unavailable policy.
+    #[test]
+    fn evacuation_overrides_all_unavailable_policies() {
+        let health = |_: &TrafficSplit, service: &str| match service {
+            "primary-east" => Health::evacuated("cluster east is evacuated"),
+            _ => Health::not_ready("no ready endpoints"),
+        };
+        let ts = traffic_split(
+            "ts0",
+            "primary-east",
+            vec![backend("primary-east", 1), backend("secondary", 0)],
+        );
+        let decision = traffic_split::decide(&ts, &health).unwrap();
+        assert!(decision.all_unavailable);
+
+        let weights = |policy| {
+            traffic_split::apply_all_unavailable(&ts, decision.clone(), policy)
+                .backends
+                .iter()
+                .map(|b| b.weight)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(weights(traffic_split::AllUnavailable::Zero), vec![0, 0]);
+        assert_eq!(weights(traffic_split::AllUnavailable::Keep), vec![0, 0]);
+        assert_eq!(weights(traffic_split::AllUnavailable::Primary), vec![0, 0]);
+    }
+
+    /// Given a traffic split whose backends were last updated by another failover controller, the
+    /// patch reports the competing controller's field manager, but not the legacy field manager of
+    /// earlier versions of this controller.
+    #[tokio::test]
+    async fn reports_competing_managers() {
+        let _log = init_tracing();
+        let (ctx, mut endpoints, mut trafficsplit, mut patches) = mk_ctx(10);
+
+        let restart_eps = Event::Restarted(vec![
+            endpoints_not_ready("primary", "10.11.12.13"),
+            endpoints_ready("secondary", "10.11.12.14"),
+        ]);
+        endpoints.apply_watcher_event(&restart_eps);
+        endpoints::handle(restart_eps, &ctx).await;
+        assert_pending!(patches.poll_next());
+
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![backend("primary", 1), backend("secondary", 0)],
+        );
+        ts.metadata.managed_fields = Some(vec![
+            ManagedFieldsEntry {
+                manager: Some("kubectl-client-side-apply".to_owned()),
+                fields_v1: Some(FieldsV1(serde_json::json!({"f:spec": {"f:service": {}}}))),
+                ..Default::default()
+            },
+            // Earlier versions of this controller used the bare prefix.
+            ManagedFieldsEntry {
+                manager: Some(traffic_split::DEFAULT_FIELD_MANAGER.to_owned()),
+                fields_v1: Some(FieldsV1(serde_json::json!({"f:spec": {"f:backends": {}}}))),
+                ..Default::default()
+            },
+            ManagedFieldsEntry {
+                manager: Some("failover.linkerd.io/other".to_owned()),
+                fields_v1: Some(FieldsV1(serde_json::json!({"f:spec": {"f:backends": {}}}))),
+                ..Default::default()
+            },
+        ]);
+        let restart_ts = Event::Restarted(vec![ts]);
+        trafficsplit.apply_watcher_event(&restart_ts);
+        traffic_split::handle(restart_ts, &ctx).await;
+
+        assert_ready_eq!(
+            patches.poll_next(),
+            Some(traffic_split::FailoverUpdate {
+                primary_active: false,
+                target: ObjectRef::new("ts0").within("default"),
+                backends: vec![backend("primary", 0), backend("secondary", 1)],
+                competing_managers: vec!["failover.linkerd.io/other".to_owned()],
+                all_unavailable: None,
+            })
+        );
+    }
 }
@@ -0,0 +1,204 @@
+//! Reports how far each backend service is from the controller's own cluster, so that splits may
+//! prefer their nearest ready fallbacks.
+//!
+//! Backend services declare where they run with the [`REGION_ANNOTATION`] and [`ZONE_ANNOTATION`],
+//! e.g. on mirror services of remote clusters. Splits opt in by setting the
+//! [`FALLBACK_PREFERENCE_ANNOTATION`] to [`NEAREST`].
+
+use crate::{Health, HealthSource, TrafficSplit};
+use k8s_openapi::api::core::v1::Service;
+use kube::{
+    runtime::{
+        reflector::{store::Writer, ObjectRef},
+        watcher,
+    },
+    ResourceExt,
+};
+use kubert::runtime::Store;
+
+/// The region a backend service runs in, e.g. `us-east-1`
+pub const REGION_ANNOTATION: &str = "failover.linkerd.io/region";
+
+/// The zone a backend service runs in, e.g. `us-east-1a`
+pub const ZONE_ANNOTATION: &str = "failover.linkerd.io/zone";
+
+/// Determines which ready fallbacks receive traffic when a split fails over: [`NEAREST`], or all
+/// of them by default
+pub const FALLBACK_PREFERENCE_ANNOTATION: &str = "failover.linkerd.io/fallback-preference";
+
+/// A [`FALLBACK_PREFERENCE_ANNOTATION`] value that only sends traffic to the nearest ready
+/// fallbacks
+pub const NEAREST: &str = "nearest";
+
+/// The distance of backends in the controller's zone
+pub const SAME_ZONE: u32 = 0;
+/// The distance of backends in the controller's region, but not its zone
+pub const SAME_REGION: u32 = 1;
+/// The distance of backends in other regions
+pub const OTHER_REGION: u32 = 2;
+/// The distance of backends that do not declare their region. They are only preferred over
+/// backends that are not ready.
+pub const UNKNOWN: u32 = 3;
+
+/// Where the controller's cluster runs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Locality {
+    pub region: String,
+    pub zone: Option<String>,
+}
+
+/// A health source that reports every backend as ready, along with its distance from the
+/// controller's locality
+#[derive(Clone)]
+pub struct LocalityHealth {
+    locality: Locality,
+    services: Store<Service>,
+}
+
+impl LocalityHealth {
+    /// Returns a health source that reads services from the given cache, e.g. one created with
+    /// [`watch_services`](crate::watch_services)
+    pub fn new(locality: Locality, services: Store<Service>) -> Self {
+        Self { locality, services }
+    }
+
+    /// Returns a health source over a snapshot of the given services, e.g. as listed by the CLI
+    pub fn from_objects(locality: Locality, services: Vec<Service>) -> Self {
+        let mut writer = Writer::default();
+        writer.apply_watcher_event(&watcher::Event::Restarted(services));
+        Self::new(locality, writer.as_reader())
+    }
+
+    fn distance(&self, region: Option<&str>, zone: Option<&str>) -> u32 {
+        match region {
+            Some(region) if region == self.locality.region => {
+                match (zone, self.locality.zone.as_deref()) {
+                    (Some(zone), Some(local)) if zone == local => SAME_ZONE,
+                    _ => SAME_REGION,
+                }
+            }
+            Some(_) => OTHER_REGION,
+            None => UNKNOWN,
+        }
+    }
+}
+
+impl HealthSource for LocalityHealth {
+    fn health(&self, split: &TrafficSplit, service: &str) -> Health {
+        let namespace = split.namespace().unwrap_or_default();
+        let svc = match self
+            .services
+            .get(&ObjectRef::new(service).within(&namespace))
+        {
+            Some(svc) => svc,
+            None => return Health::ready("").with_distance(UNKNOWN),
+        };
+        let region = svc.annotations().get(REGION_ANNOTATION).map(String::as_str);
+        let zone = svc.annotations().get(ZONE_ANNOTATION).map(String::as_str);
+        let distance = self.distance(region, zone);
+        let detail = match (region, zone) {
+            (Some(region), Some(zone)) => format!("in {region}/{zone}"),
+            (Some(region), None) => format!("in {region}"),
+            _ => String::new(),
+        };
+        Health::ready(detail).with_distance(distance)
+    }
+}
+
+/// Returns true if the split only sends traffic to its nearest ready fallbacks
+pub fn prefers_nearest(split: &TrafficSplit) -> bool {
+    split
+        .annotations()
+        .get(FALLBACK_PREFERENCE_ANNOTATION)
+        .map_or(false, |p| p == NEAREST)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        health::AllOf,
+        tests::{backend, traffic_split},
+        traffic_split,
+    };
+    use kube::runtime::watcher::Event;
+    use std::sync::Arc;
+
+    /// Splits that prefer their nearest fallbacks only fail over to the nearest ready ones.
+    #[test]
+    fn prefers_nearest_ready_fallbacks() {
+        let mut services = Writer::default();
+        let svc = |name: &str, region: Option<&str>, zone: Option<&str>| {
+            let mut svc = k8s_openapi::api::core::v1::Service::default();
+            svc.metadata.name = Some(name.to_owned());
+            svc.metadata.namespace = Some("default".to_owned());
+            for (annotation, value) in [(REGION_ANNOTATION, region), (ZONE_ANNOTATION, zone)] {
+                if let Some(value) = value {
+                    svc.annotations_mut()
+                        .insert(annotation.to_owned(), value.to_owned());
+                }
+            }
+            svc
+        };
+        services.apply_watcher_event(&Event::Restarted(vec![
+            svc("remote", Some("eu-west"), None),
+            svc("regional", Some("us-east"), Some("us-east-1b")),
+            svc("local", Some("us-east"), Some("us-east-1a")),
+        ]));
+        let locality = LocalityHealth::new(
+            Locality {
+                region: "us-east".to_owned(),
+                zone: Some("us-east-1a".to_owned()),
+            },
+            services.as_reader(),
+        );
+
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![
+                backend("primary", 1),
+                backend("unknown", 0),
+                backend("remote", 0),
+                backend("regional", 0),
+                backend("local", 0),
+            ],
+        );
+        ts.annotations_mut().insert(
+            FALLBACK_PREFERENCE_ANNOTATION.to_owned(),
+            NEAREST.to_owned(),
+        );
+        let weights = |ts: &TrafficSplit, down: &'static [&'static str]| {
+            let readiness = move |_: &TrafficSplit, service: &str| {
+                if down.contains(&service) {
+                    Health::not_ready("")
+                } else {
+                    Health::ready("")
+                }
+            };
+            let health = AllOf(vec![Arc::new(readiness), Arc::new(locality.clone())]);
+            traffic_split::decide(ts, &health)
+                .unwrap()
+                .backends
+                .iter()
+                .map(|b| b.weight)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(weights(&ts, &["primary"]), vec![0, 0, 0, 0, 1]);
+        assert_eq!(weights(&ts, &["primary", "local"]), vec![0, 0, 0, 1, 0]);
+        assert_eq!(
+            weights(&ts, &["primary", "local", "regional"]),
+            vec![0, 0, 1, 0, 0]
+        );
+        // Backends that don't declare their region are used last.
+        assert_eq!(
+            weights(&ts, &["primary", "local", "regional", "remote"]),
+            vec![0, 1, 0, 0, 0]
+        );
+
+        // Other splits spread traffic across all ready fallbacks.
+        ts.annotations_mut().remove(FALLBACK_PREFERENCE_ANNOTATION);
+        assert_eq!(weights(&ts, &["primary"]), vec![0, 1, 1, 1, 1]);
+    }
+}
@@ -3,10 +3,15 @@
 
 use anyhow::{bail, Result};
 use clap::Parser;
-use kube::runtime::watcher::Config;
-use linkerd_failover_controller::{endpoints, traffic_split, Ctx};
-use tokio::{sync::mpsc, time};
-use tracing::Instrument;
+use linkerd_failover_controller::{
+    locality::{Locality, LocalityHealth},
+    multicluster::{self, MulticlusterHealth},
+    probe::{self, Prober},
+    prometheus::{self, PrometheusHealth},
+    traffic_split::AllUnavailable,
+    watch_services, FailoverController, DEFAULT_SELECTOR,
+};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(version)]
@@ -27,8 +32,83 @@ struct Args {
     #[command(flatten)]
     admin: kubert::AdminArgs,
 
-    #[arg(long, default_value = "failover.linkerd.io/controlled-by", short = 'l')]
+    #[arg(long, default_value = DEFAULT_SELECTOR, short = 'l')]
     selector: String,
+
+    /// Field manager used to patch TrafficSplits. Each controller should use a distinct field
+    /// manager so that competing controllers can be detected. Defaults to
+    /// `failover.linkerd.io/<namespace>`, where the namespace is the controller's own.
+    #[arg(long)]
+    field_manager: Option<String>,
+
+    /// URL of a Prometheus API with Linkerd proxy metrics. When set, TrafficSplits may fail over
+    /// based on their backends' success rate and latency.
+    #[arg(long)]
+    prometheus_url: Option<hyper::Uri>,
+
+    /// How often metrics are queried from Prometheus
+    #[arg(long, default_value = "10s", value_parser = humantime::parse_duration)]
+    metrics_interval: Duration,
+
+    /// How long a backend's metrics must breach its TrafficSplit's thresholds before it fails
+    /// over, unless the TrafficSplit sets its own window
+    #[arg(long, default_value = "1m", value_parser = humantime::parse_duration)]
+    failure_window: Duration,
+
+    /// The longest failure window a TrafficSplit may set
+    #[arg(long, default_value = "10m", value_parser = humantime::parse_duration)]
+    max_failure_window: Duration,
+
+    /// Actively probes backend Services annotated with `failover.linkerd.io/probe-type`, so that
+    /// backends that fail their probes do not receive traffic
+    #[arg(long)]
+    enable_probes: bool,
+
+    /// How often backend Services are probed
+    #[arg(long, default_value = "10s", value_parser = humantime::parse_duration)]
+    probe_interval: Duration,
+
+    /// How long to wait for a probe's response
+    #[arg(long, default_value = "2s", value_parser = humantime::parse_duration)]
+    probe_timeout: Duration,
+
+    /// How many consecutive successful probes make an unhealthy backend healthy
+    #[arg(long, default_value = "1")]
+    probe_success_threshold: u32,
+
+    /// How many consecutive failed probes make a healthy backend unhealthy
+    #[arg(long, default_value = "3")]
+    probe_failure_threshold: u32,
+
+    /// Considers Linkerd multicluster mirror services unavailable while their remote cluster's
+    /// gateway is down
+    #[arg(long)]
+    enable_multicluster: bool,
+
+    /// Namespace that Linkerd multicluster is installed into
+    #[arg(long, default_value = multicluster::DEFAULT_NAMESPACE)]
+    multicluster_namespace: String,
+
+    /// How often TrafficSplits are re-evaluated against the health of remote gateways
+    #[arg(long, default_value = "10s", value_parser = humantime::parse_duration)]
+    multicluster_resync_interval: Duration,
+
+    /// Region the controller's cluster runs in. When set, TrafficSplits may prefer the ready
+    /// fallbacks nearest to it, as declared by their Services' `failover.linkerd.io/region`
+    /// annotations
+    #[arg(long)]
+    region: Option<String>,
+
+    /// Zone the controller's cluster runs in, within its region
+    #[arg(long, requires = "region")]
+    zone: Option<String>,
+
+    /// How TrafficSplits are weighted while their primary and all of their fallbacks are
+    /// unavailable: `zero` removes all traffic, `keep` keeps the last weights and `primary` sends
+    /// all traffic to the primary. TrafficSplits may override this with the
+    /// `failover.linkerd.io/all-unavailable` annotation.
+    #[arg(long, default_value = "zero")]
+    all_unavailable: AllUnavailable,
 }
 
 #[tokio::main]
@@ -39,6 +119,22 @@ async fn main() -> Result<()> {
         client,
         admin,
         selector,
+        field_manager,
+        prometheus_url,
+        metrics_interval,
+        failure_window,
+        max_failure_window,
+        enable_probes,
+        probe_interval,
+        probe_timeout,
+        probe_success_threshold,
+        probe_failure_threshold,
+        enable_multicluster,
+        multicluster_namespace,
+        multicluster_resync_interval,
+        region,
+        zone,
+        all_unavailable,
     } = Args::parse();
 
     let mut runtime = kubert::Runtime::builder()
@@ -48,50 +144,67 @@ async fn main() -> Result<()> {
         .build()
         .await?;
 
-    // Create cached watches for traffic splits and endpoints. This enables us to watch for
-    // updates and to lookup previously-observed objects.
-    let (endpoints, endpoints_events) = runtime.cache_all(Config::default());
-    let (traffic_splits, traffic_split_events) =
-        runtime.cache_all(Config::default().labels(&selector));
-
-    // This should be large enough to handle all traffic splits in the cluster so that a restart
-    // doesn't completely fill the queue; but it shouldn't be so large that slow writes can cause
-    // the process to balloon memory usage.
-    let (patches_tx, patches_rx) = mpsc::channel(1000);
-
-    // We spawn the watches on a single task to avoid cache coherency issues caused by
-    // concurrent updates. For example, when processing a traffic split update, we'll iterate
-    // through its backends and look up the endpoint for each. We don't want the endpoint states
-    // to change while looping--for example, changing the state of the primary backend. By
-    // spawning both watches on a single task, we ensure that the cache cannot be updated while
-    // an update is being processed.
-
-    tokio::spawn(async move {
-        let ctx = Ctx {
-            endpoints,
-            traffic_splits,
-            patches: patches_tx,
-        };
-        let eps = endpoints::process(endpoints_events, ctx.clone())
-            .instrument(tracing::info_span!("endpoints"));
-        let ts = traffic_split::process(traffic_split_events, ctx)
-            .instrument(tracing::info_span!("trafficsplit"));
-        tokio::join!(eps, ts);
-    });
-
-    // Spawn a task that applies TrafficSplit patches when either of the above watches detect
-    // changes. This helps to ensure to prevent conflicting patches by serializing all updates on a
-    // single task.
-    const WRITE_TIMEOUT: time::Duration = time::Duration::from_secs(10);
-    tokio::spawn(
-        runtime
-            .cancel_on_shutdown(traffic_split::apply_patches(
-                patches_rx,
-                runtime.client(),
-                WRITE_TIMEOUT,
-            ))
-            .instrument(tracing::info_span!("patch")),
-    );
+    let mut controller = FailoverController::builder(runtime.client())
+        .runtime(&mut runtime)
+        .selector(selector)
+        .all_unavailable(all_unavailable);
+    if let Some(field_manager) = field_manager {
+        controller = controller.field_manager(field_manager);
+    }
+    let mut resync_interval = None;
+    if let Some(url) = prometheus_url {
+        let (health, poll) = PrometheusHealth::new(prometheus::Config {
+            url,
+            interval: metrics_interval,
+            default_window: failure_window,
+            max_window: max_failure_window,
+        })?;
+        tokio::spawn(runtime.cancel_on_shutdown(poll));
+        controller = controller.health_check(health);
+        resync_interval = Some(metrics_interval);
+    }
+    // Probes and locality share a single cache of services.
+    let services = if enable_probes || region.is_some() {
+        let (services, watch) = watch_services(runtime.client());
+        tokio::spawn(runtime.cancel_on_shutdown(watch));
+        Some(services)
+    } else {
+        None
+    };
+    if let Some(services) = services.clone().filter(|_| enable_probes) {
+        let (health, probes) = Prober::new(
+            runtime.client(),
+            services,
+            probe::Config {
+                interval: probe_interval,
+                timeout: probe_timeout,
+                success_threshold: probe_success_threshold,
+                failure_threshold: probe_failure_threshold,
+            },
+        );
+        tokio::spawn(runtime.cancel_on_shutdown(probes));
+        controller = controller.health_check(health);
+        resync_interval = Some(resync_interval.map_or(probe_interval, |i| i.min(probe_interval)));
+    }
+    if enable_multicluster {
+        let (health, watches) = MulticlusterHealth::new(runtime.client(), multicluster_namespace);
+        tokio::spawn(runtime.cancel_on_shutdown(watches));
+        controller = controller.health_check(health);
+        resync_interval = Some(resync_interval.map_or(multicluster_resync_interval, |i| {
+            i.min(multicluster_resync_interval)
+        }));
+    }
+    if let (Some(region), Some(services)) = (region, services) {
+        let health = LocalityHealth::new(Locality { region, zone }, services);
+        controller = controller.health_check(health);
+    }
+    // Health checks change independently of the watched resources, so splits are re-evaluated as
+    // often as the checks are updated.
+    if let Some(interval) = resync_interval {
+        controller = controller.resync_interval(interval);
+    }
+    let controller = controller.build();
+    tokio::spawn(controller.run());
 
     // Block the main thread on the shutdown signal. Once it fires, wait for the background tasks to
     // complete before exiting.
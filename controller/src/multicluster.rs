@@ -0,0 +1,277 @@
+//! Reports mirror services created by Linkerd multicluster as unhealthy when the gateway of their
+//! remote cluster is down.
+//!
+//! A mirror service's `Endpoints` point at its remote cluster's gateway, so they stay ready while
+//! the gateway is unreachable. The service mirror controller probes each linked cluster's gateway
+//! and tracks the result in the `Endpoints` of the cluster's gateway mirror service,
+//! `probe-gateway-<cluster>`, in the multicluster namespace. A mirror service is only ready while
+//! its cluster is linked and its gateway mirror has ready addresses.
+//!
+//! A cluster may also be evacuated by setting the [`EVACUATE_ANNOTATION`] on its `Link`, which
+//! makes all of its mirror services unavailable until the annotation is removed.
+
+use crate::{controller::cache, endpoints, Endpoints, Health, HealthSource, TrafficSplit};
+use futures::prelude::*;
+use k8s_openapi::api::core::v1::Service;
+use kube::{
+    runtime::{
+        reflector::{store::Writer, ObjectRef},
+        watcher,
+    },
+    Api, ResourceExt,
+};
+use kubert::runtime::Store;
+
+/// Names the remote cluster of a mirror service
+pub const CLUSTER_NAME_LABEL: &str = "mirror.linkerd.io/cluster-name";
+
+/// Set to `true` on a [`Link`] to move traffic off of all of the remote cluster's mirror services
+pub const EVACUATE_ANNOTATION: &str = "failover.linkerd.io/evacuate";
+
+/// The namespace that Linkerd multicluster is installed into, unless otherwise configured
+pub const DEFAULT_NAMESPACE: &str = "linkerd-multicluster";
+
+/// The `multicluster.linkerd.io/Link` custom resource, which links a remote cluster
+#[derive(
+    Clone,
+    Debug,
+    Default,
+    kube::CustomResource,
+    serde::Deserialize,
+    serde::Serialize,
+    schemars::JsonSchema,
+)]
+#[kube(
+    group = "multicluster.linkerd.io",
+    version = "v1alpha1",
+    kind = "Link",
+    namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkSpec {
+    #[serde(default)]
+    pub target_cluster_name: String,
+    pub gateway_address: Option<String>,
+    pub gateway_port: Option<String>,
+    pub probe_spec: Option<ProbeSpec>,
+}
+
+/// How the service mirror controller probes a [`Link`]'s gateway
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+pub struct ProbeSpec {
+    pub path: Option<String>,
+    pub port: Option<String>,
+    pub period: Option<String>,
+}
+
+/// A health source backed by cached mirror services, links and gateway mirror endpoints
+#[derive(Clone)]
+pub struct MulticlusterHealth {
+    namespace: String,
+    services: Store<Service>,
+    gateways: Store<Endpoints>,
+    links: Store<Link>,
+}
+
+impl MulticlusterHealth {
+    /// Returns the health source along with a future that watches mirror services, and the links
+    /// and gateway mirrors in `namespace`, which must be spawned for the source to observe them.
+    pub fn new(
+        client: kube::Client,
+        namespace: impl Into<String>,
+    ) -> (Self, impl Future<Output = ()>) {
+        let namespace = namespace.into();
+        let (services, services_events) = cache(
+            Api::<Service>::all(client.clone()),
+            watcher::Config::default().labels(CLUSTER_NAME_LABEL),
+            None,
+        );
+        let (gateways, gateways_events) = cache(
+            Api::<Endpoints>::namespaced(client.clone(), &namespace),
+            watcher::Config::default(),
+            None,
+        );
+        let (links, links_events) = cache(
+            Api::<Link>::namespaced(client, &namespace),
+            watcher::Config::default(),
+            None,
+        );
+        let watches = async move {
+            tokio::join!(
+                services_events.for_each(|_| future::ready(())),
+                gateways_events.for_each(|_| future::ready(())),
+                links_events.for_each(|_| future::ready(())),
+            );
+        };
+        (
+            Self::from_stores(namespace, services, gateways, links),
+            watches,
+        )
+    }
+
+    /// Returns a health source over a snapshot of the given resources, e.g. as listed by the CLI
+    pub fn from_objects(
+        namespace: impl Into<String>,
+        services: Vec<Service>,
+        gateways: Vec<Endpoints>,
+        links: Vec<Link>,
+    ) -> Self {
+        fn store<K>(objects: Vec<K>) -> Store<K>
+        where
+            K: kube::Resource + Clone,
+            K::DynamicType: Clone + Default + Eq + std::hash::Hash,
+        {
+            let mut writer = Writer::default();
+            writer.apply_watcher_event(&watcher::Event::Restarted(objects));
+            writer.as_reader()
+        }
+        Self::from_stores(namespace, store(services), store(gateways), store(links))
+    }
+
+    /// Returns a health source that reads from the given caches
+    fn from_stores(
+        namespace: impl Into<String>,
+        services: Store<Service>,
+        gateways: Store<Endpoints>,
+        links: Store<Link>,
+    ) -> Self {
+        Self {
+            namespace: namespace.into(),
+            services,
+            gateways,
+            links,
+        }
+    }
+}
+
+impl HealthSource for MulticlusterHealth {
+    fn health(&self, split: &TrafficSplit, service: &str) -> Health {
+        let namespace = split.namespace().unwrap_or_default();
+        let cluster = match self
+            .services
+            .get(&ObjectRef::new(service).within(&namespace))
+            .and_then(|svc| svc.labels().get(CLUSTER_NAME_LABEL).cloned())
+        {
+            Some(cluster) => cluster,
+            // Local services are not affected by remote gateways.
+            None => return Health::ready(""),
+        };
+
+        let link = self
+            .links
+            .state()
+            .into_iter()
+            .find(|link| link.spec.target_cluster_name == cluster);
+        let link = match link {
+            Some(link) => link,
+            None => return Health::not_ready(format!("cluster {cluster} is not linked")),
+        };
+        if is_evacuated(&link) {
+            return Health::evacuated(format!("cluster {cluster} is evacuated"));
+        }
+        let gateway = match (&link.spec.gateway_address, &link.spec.gateway_port) {
+            (Some(address), Some(port)) => format!("gateway {address}:{port}"),
+            _ => "gateway".to_string(),
+        };
+
+        let gateway_mirror = format!("probe-gateway-{cluster}");
+        let eps = self
+            .gateways
+            .get(&ObjectRef::new(&gateway_mirror).within(&self.namespace));
+        match eps {
+            Some(eps) if endpoints::is_ready(&eps) => {
+                Health::ready(format!("cluster {cluster}: {gateway} is alive"))
+            }
+            Some(_) => Health::not_ready(format!("cluster {cluster}: {gateway} is down")),
+            None => Health::not_ready(format!(
+                "cluster {cluster}: {gateway} has no gateway mirror {}/{gateway_mirror}",
+                self.namespace
+            )),
+        }
+    }
+}
+
+/// Returns true if the link's cluster has been evacuated
+pub fn is_evacuated(link: &Link) -> bool {
+    link.annotations()
+        .get(EVACUATE_ANNOTATION)
+        .map_or(false, |v| v == "true")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{
+        backend, endpoints_not_ready, endpoints_ready, init_tracing, traffic_split,
+    };
+    use kube::runtime::watcher::Event;
+
+    /// Given mirror services of a linked cluster, they are ready only while the cluster's gateway
+    /// is up and the cluster is not evacuated, while local services are unaffected.
+    #[test]
+    fn mirror_services_follow_their_gateway() {
+        let _log = init_tracing();
+        let mut services = Writer::default();
+        let mut gateways = Writer::default();
+        let mut links = Writer::default();
+        let health = MulticlusterHealth::from_stores(
+            DEFAULT_NAMESPACE,
+            services.as_reader(),
+            gateways.as_reader(),
+            links.as_reader(),
+        );
+        let ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![backend("primary", 1), backend("secondary-east", 0)],
+        );
+
+        let mut mirror = k8s_openapi::api::core::v1::Service::default();
+        mirror.metadata.name = Some("secondary-east".to_owned());
+        mirror.metadata.namespace = Some("default".to_owned());
+        mirror
+            .labels_mut()
+            .insert(CLUSTER_NAME_LABEL.to_owned(), "east".to_owned());
+        services.apply_watcher_event(&Event::Restarted(vec![mirror]));
+
+        // Local services are unaffected, while mirrors of unlinked clusters are unavailable.
+        assert_eq!(health.health(&ts, "primary"), Health::ready(""));
+        assert!(!health.health(&ts, "secondary-east").ready);
+
+        let mut link = Link::new(
+            "east",
+            LinkSpec {
+                target_cluster_name: "east".to_owned(),
+                ..Default::default()
+            },
+        );
+        link.metadata.namespace = Some(DEFAULT_NAMESPACE.to_owned());
+        links.apply_watcher_event(&Event::Restarted(vec![link.clone()]));
+        assert!(!health.health(&ts, "secondary-east").ready);
+
+        let mut gateway = endpoints_ready("probe-gateway-east", "10.11.12.13");
+        gateway.metadata.namespace = Some(DEFAULT_NAMESPACE.to_owned());
+        gateways.apply_watcher_event(&Event::Applied(gateway));
+        assert!(health.health(&ts, "secondary-east").ready);
+
+        // Evacuated clusters are unavailable regardless of their gateway.
+        let mut evacuated = link.clone();
+        evacuated
+            .annotations_mut()
+            .insert(EVACUATE_ANNOTATION.to_owned(), "true".to_owned());
+        links.apply_watcher_event(&Event::Applied(evacuated));
+        assert_eq!(
+            health.health(&ts, "secondary-east"),
+            Health::evacuated("cluster east is evacuated")
+        );
+        links.apply_watcher_event(&Event::Applied(link));
+        assert!(health.health(&ts, "secondary-east").ready);
+
+        let mut gateway = endpoints_not_ready("probe-gateway-east", "10.11.12.13");
+        gateway.metadata.namespace = Some(DEFAULT_NAMESPACE.to_owned());
+        gateways.apply_watcher_event(&Event::Applied(gateway));
+        let health = health.health(&ts, "secondary-east");
+        assert!(!health.ready);
+        assert_eq!(health.detail, "cluster east: gateway is down");
+    }
+}
@@ -0,0 +1,622 @@
+//! Actively probes backend services, so that backends whose endpoints appear ready but cannot be
+//! reached, e.g. mirror services of an unreachable cluster, are not sent traffic.
+//!
+//! Services opt in by setting a [`PROBE_TYPE_ANNOTATION`] or a [`PROBE_PATH_ANNOTATION`]. Each
+//! interval, the prober probes the service's cluster IP with an HTTP GET, a TCP connect, or a
+//! `grpc.health.v1.Health/Check` call. A service becomes unhealthy after a number of consecutive
+//! failed probes and healthy again after a number of consecutive successful ones. These
+//! transitions are recorded as events on the service.
+
+use crate::{Health, HealthSource, TrafficSplit};
+use futures::prelude::*;
+use hyper::{body::HttpBody, client::HttpConnector, header, Body, Request};
+use k8s_openapi::api::core::v1::Service;
+use kube::{
+    runtime::{events, reflector::ObjectRef},
+    ResourceExt,
+};
+use kubert::runtime::Store;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    net::TcpStream,
+    time::{self, Duration},
+};
+
+/// How a backend service is probed: `http` (the default), `tcp`, or `grpc`
+pub const PROBE_TYPE_ANNOTATION: &str = "failover.linkerd.io/probe-type";
+
+/// The path of HTTP probes, e.g. `/ready`. Defaults to `/`.
+pub const PROBE_PATH_ANNOTATION: &str = "failover.linkerd.io/probe-path";
+
+/// The service port to probe, by number or name. Defaults to the service's first port.
+pub const PROBE_PORT_ANNOTATION: &str = "failover.linkerd.io/probe-port";
+
+/// The service name sent in gRPC health checks. Defaults to the empty name, which checks the
+/// server's overall health.
+pub const PROBE_GRPC_SERVICE_ANNOTATION: &str = "failover.linkerd.io/probe-grpc-service";
+
+/// The reason of the warning events recorded when a service fails its probes
+pub const PROBE_FAILED: &str = "ProbeFailed";
+
+/// The reason of the events recorded when a service passes its probes again
+pub const PROBE_SUCCEEDED: &str = "ProbeSucceeded";
+
+const CONTROLLER_NAME: &str = "linkerd-failover";
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How often each service is probed
+    pub interval: Duration,
+    /// How long to wait for a probe's response
+    pub timeout: Duration,
+    /// How many consecutive successful probes make an unhealthy service healthy
+    pub success_threshold: u32,
+    /// How many consecutive failed probes make a healthy service unhealthy
+    pub failure_threshold: u32,
+}
+
+/// A health source backed by the results of probing each annotated service
+#[derive(Clone)]
+pub struct Prober {
+    config: Config,
+    states: Arc<Mutex<HashMap<(String, String), ProbeState>>>,
+}
+
+#[derive(Clone, Debug)]
+struct ProbeState {
+    healthy: bool,
+    successes: u32,
+    failures: u32,
+    /// Describes the last probe's result
+    last: String,
+}
+
+/// Where and how a service is probed
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Target {
+    namespace: String,
+    service: String,
+    /// The cluster IP and port of the service
+    addr: SocketAddr,
+    kind: ProbeKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ProbeKind {
+    Http { path: String },
+    Tcp,
+    Grpc { service: String },
+}
+
+/// The clients shared by all probes
+struct Clients {
+    http: hyper::Client<HttpConnector>,
+    grpc: hyper::Client<HttpConnector>,
+}
+
+impl Prober {
+    /// Returns the health source along with a future that probes the annotated services in the
+    /// given cache, which must be spawned for the source to observe any results.
+    pub fn new(
+        client: kube::Client,
+        services: Store<Service>,
+        config: Config,
+    ) -> (Self, impl Future<Output = ()>) {
+        let prober = Self::from_config(config);
+        (prober.clone(), prober.run(client, services))
+    }
+
+    /// Returns a health source that only observes the results passed to [`Prober::observe`]
+    fn from_config(config: Config) -> Self {
+        Self {
+            config,
+            states: Default::default(),
+        }
+    }
+
+    /// Records the result of probing a service, updating its health once enough consecutive
+    /// probes agree. Returns the service's new health if it changed.
+    fn observe(
+        &self,
+        namespace: &str,
+        service: &str,
+        result: Result<String, String>,
+    ) -> Option<Health> {
+        let mut states = self.states.lock().expect("probe states lock poisoned");
+        let state = states
+            .entry((namespace.to_string(), service.to_string()))
+            .or_insert_with(|| ProbeState {
+                // Services are assumed to be healthy until probes show otherwise, so that enabling
+                // probes does not move traffic.
+                healthy: true,
+                successes: 0,
+                failures: 0,
+                last: String::new(),
+            });
+        match result {
+            Ok(detail) => {
+                state.successes += 1;
+                state.failures = 0;
+                state.last = detail;
+                if !state.healthy && state.successes >= self.config.success_threshold {
+                    tracing::info!(%namespace, %service, "probes succeeded; service is healthy");
+                    state.healthy = true;
+                    return Some(state.health());
+                }
+            }
+            Err(detail) => {
+                state.failures += 1;
+                state.successes = 0;
+                state.last = detail;
+                if state.healthy && state.failures >= self.config.failure_threshold {
+                    tracing::info!(%namespace, %service, probe = %state.last, "probes failed; service is unhealthy");
+                    state.healthy = false;
+                    return Some(state.health());
+                }
+            }
+        }
+        None
+    }
+
+    async fn run(self, client: kube::Client, services: Store<Service>) {
+        let clients = Clients {
+            http: hyper::Client::new(),
+            grpc: hyper::Client::builder().http2_only(true).build_http(),
+        };
+        let mut interval = time::interval(self.config.interval);
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let targets = services
+                .state()
+                .iter()
+                .filter_map(|svc| target(svc))
+                .collect::<Vec<_>>();
+            let results = future::join_all(
+                targets
+                    .iter()
+                    .map(|target| probe(&clients, target, self.config.timeout)),
+            )
+            .await;
+            for (target, result) in targets.iter().zip(results) {
+                tracing::debug!(namespace = %target.namespace, service = %target.service, ?result);
+                if let Some(health) = self.observe(&target.namespace, &target.service, result) {
+                    tokio::spawn(record_event(client.clone(), target.clone(), health));
+                }
+            }
+
+            // Forget services that are no longer probed.
+            let mut states = self.states.lock().expect("probe states lock poisoned");
+            states.retain(|(ns, svc), _| {
+                targets
+                    .iter()
+                    .any(|t| t.namespace == *ns && t.service == *svc)
+            });
+        }
+    }
+}
+
+impl HealthSource for Prober {
+    fn health(&self, split: &TrafficSplit, service: &str) -> Health {
+        let namespace = split.namespace().unwrap_or_default();
+        let states = self.states.lock().expect("probe states lock poisoned");
+        match states.get(&(namespace, service.to_string())) {
+            None => Health::ready(""),
+            Some(state) => state.health(),
+        }
+    }
+}
+
+impl ProbeState {
+    fn health(&self) -> Health {
+        if self.healthy {
+            Health::ready(format!("probe: {}", self.last))
+        } else {
+            Health::not_ready(format!(
+                "probe: {} ({} consecutive failures)",
+                self.last, self.failures
+            ))
+        }
+    }
+}
+
+/// Returns true if the service is annotated for probing
+pub fn is_probed(svc: &Service) -> bool {
+    let annotations = svc.annotations();
+    annotations.contains_key(PROBE_TYPE_ANNOTATION)
+        || annotations.contains_key(PROBE_PATH_ANNOTATION)
+}
+
+/// Returns the service's probe target if it is annotated for probing
+fn target(svc: &Service) -> Option<Target> {
+    if !is_probed(svc) {
+        return None;
+    }
+    let annotations = svc.annotations();
+    let kind = match annotations
+        .get(PROBE_TYPE_ANNOTATION)
+        .map_or("http", |t| t.as_str())
+    {
+        "http" => {
+            let path = annotations
+                .get(PROBE_PATH_ANNOTATION)
+                .map_or("/", |p| p.as_str());
+            let path = if path.starts_with('/') {
+                path.to_string()
+            } else {
+                format!("/{path}")
+            };
+            ProbeKind::Http { path }
+        }
+        "tcp" => ProbeKind::Tcp,
+        "grpc" => ProbeKind::Grpc {
+            service: annotations
+                .get(PROBE_GRPC_SERVICE_ANNOTATION)
+                .cloned()
+                .unwrap_or_default(),
+        },
+        probe_type => {
+            tracing::warn!(service = %svc.name_any(), %probe_type, "ignoring invalid probe type");
+            return None;
+        }
+    };
+
+    let spec = svc.spec.as_ref()?;
+    let ip = spec
+        .cluster_ip
+        .as_deref()
+        .and_then(|ip| ip.parse::<IpAddr>().ok())?;
+    let ports = spec.ports.as_deref().unwrap_or_default();
+    let port = match annotations.get(PROBE_PORT_ANNOTATION) {
+        Some(port) => ports
+            .iter()
+            .find(|p| p.name.as_deref() == Some(port) || p.port.to_string() == *port)
+            .map(|p| p.port)
+            .or_else(|| port.parse().ok())?,
+        None => ports.first()?.port,
+    };
+    let port = u16::try_from(port).ok()?;
+    Some(Target {
+        namespace: svc.namespace()?,
+        service: svc.name_any(),
+        addr: SocketAddr::new(ip, port),
+        kind,
+    })
+}
+
+/// Probes the target, returning a description of the result
+async fn probe(clients: &Clients, target: &Target, timeout: Duration) -> Result<String, String> {
+    match &target.kind {
+        ProbeKind::Http { path } => {
+            let uri = format!("http://{}{path}", target.addr);
+            http(&clients.http, &uri, timeout).await
+        }
+        ProbeKind::Tcp => match time::timeout(timeout, TcpStream::connect(target.addr)).await {
+            Err(_) => Err(format!("connecting to {} timed out", target.addr)),
+            Ok(Err(error)) => Err(format!("connecting to {} failed: {error}", target.addr)),
+            Ok(Ok(_)) => Ok(format!("connected to {}", target.addr)),
+        },
+        ProbeKind::Grpc { service } => {
+            match time::timeout(timeout, grpc(&clients.grpc, target.addr, service)).await {
+                Err(_) => Err(format!("gRPC health check of {} timed out", target.addr)),
+                Ok(result) => result,
+            }
+        }
+    }
+}
+
+/// Sends a GET to the URI, succeeding on any 2xx or 3xx response
+async fn http(
+    client: &hyper::Client<HttpConnector>,
+    uri: &str,
+    timeout: Duration,
+) -> Result<String, String> {
+    let req = Request::get(uri)
+        .body(Body::empty())
+        .map_err(|e| e.to_string())?;
+    match time::timeout(timeout, client.request(req)).await {
+        Err(_) => Err(format!("GET {uri} timed out")),
+        Ok(Err(error)) => Err(format!("GET {uri} failed: {error}")),
+        Ok(Ok(rsp)) if rsp.status().is_success() || rsp.status().is_redirection() => {
+            Ok(format!("GET {uri} returned HTTP {}", rsp.status()))
+        }
+        Ok(Ok(rsp)) => Err(format!("GET {uri} returned HTTP {}", rsp.status())),
+    }
+}
+
+/// Calls `grpc.health.v1.Health/Check`, succeeding if the server reports the service as `SERVING`
+async fn grpc(
+    client: &hyper::Client<HttpConnector>,
+    addr: SocketAddr,
+    service: &str,
+) -> Result<String, String> {
+    let uri = format!("http://{addr}/grpc.health.v1.Health/Check");
+    let req = Request::post(&uri)
+        .header(header::CONTENT_TYPE, "application/grpc")
+        .header(header::TE, "trailers")
+        .body(Body::from(encode_health_request(service)))
+        .map_err(|e| e.to_string())?;
+    let fail = |error: String| format!("gRPC health check of {addr} failed: {error}");
+
+    let rsp = client.request(req).await.map_err(|e| fail(e.to_string()))?;
+    if !rsp.status().is_success() {
+        return Err(fail(format!("HTTP {}", rsp.status())));
+    }
+    // Errors may be returned without a body, in which case the status is sent in the headers
+    // rather than the trailers.
+    let mut status = grpc_status(rsp.headers());
+    let mut body = rsp.into_body();
+    let mut message = Vec::new();
+    while let Some(chunk) = body.data().await {
+        message.extend_from_slice(&chunk.map_err(|e| fail(e.to_string()))?);
+    }
+    if let Some(trailers) = body.trailers().await.map_err(|e| fail(e.to_string()))? {
+        status = status.or_else(|| grpc_status(&trailers));
+    }
+    match status {
+        Some(0) => {}
+        Some(code) => return Err(fail(format!("gRPC status {code}"))),
+        None => return Err(fail("missing gRPC status".to_string())),
+    }
+
+    match decode_health_response(&message).map_err(fail)? {
+        1 => Ok(format!("gRPC health check of {addr} returned SERVING")),
+        status => Err(fail(format!(
+            "server returned {}",
+            serving_status_name(status)
+        ))),
+    }
+}
+
+fn grpc_status(headers: &hyper::HeaderMap) -> Option<u32> {
+    headers.get("grpc-status")?.to_str().ok()?.parse().ok()
+}
+
+/// Encodes a length-prefixed `HealthCheckRequest` message
+fn encode_health_request(service: &str) -> Vec<u8> {
+    // The request's only field is `string service = 1`, which is omitted when empty.
+    let mut message = Vec::new();
+    if !service.is_empty() {
+        message.push(0x0a);
+        encode_varint(service.len() as u64, &mut message);
+        message.extend_from_slice(service.as_bytes());
+    }
+    let mut frame = Vec::with_capacity(5 + message.len());
+    frame.push(0); // uncompressed
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&message);
+    frame
+}
+
+/// Decodes a length-prefixed `HealthCheckResponse` message, returning its serving status
+fn decode_health_response(frame: &[u8]) -> Result<u64, String> {
+    let (header, rest) = match frame {
+        [compressed, a, b, c, d, rest @ ..] => ((*compressed, [*a, *b, *c, *d]), rest),
+        _ => return Err("missing response message".to_string()),
+    };
+    if header.0 != 0 {
+        return Err("compressed responses are not supported".to_string());
+    }
+    let len = u32::from_be_bytes(header.1) as usize;
+    let mut message = rest
+        .get(..len)
+        .ok_or_else(|| "truncated response message".to_string())?;
+
+    // The response's only field is `ServingStatus status = 1`, which is `UNKNOWN` when omitted.
+    // Unknown fields are skipped.
+    let mut status = 0;
+    while !message.is_empty() {
+        let key = decode_varint(&mut message)?;
+        match key & 0x7 {
+            0 => {
+                let value = decode_varint(&mut message)?;
+                if key >> 3 == 1 {
+                    status = value;
+                }
+            }
+            1 => message = message.get(8..).ok_or("truncated field")?,
+            2 => {
+                let len = decode_varint(&mut message)? as usize;
+                message = message.get(len..).ok_or("truncated field")?;
+            }
+            5 => message = message.get(4..).ok_or("truncated field")?,
+            wire_type => return Err(format!("unsupported wire type {wire_type}")),
+        }
+    }
+    Ok(status)
+}
+
+fn serving_status_name(status: u64) -> String {
+    match status {
+        0 => "UNKNOWN".to_string(),
+        1 => "SERVING".to_string(),
+        2 => "NOT_SERVING".to_string(),
+        3 => "SERVICE_UNKNOWN".to_string(),
+        status => format!("status {status}"),
+    }
+}
+
+fn encode_varint(mut value: u64, buf: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn decode_varint(buf: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = buf
+            .split_first()
+            .ok_or_else(|| "truncated varint".to_string())?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("invalid varint".to_string())
+}
+
+async fn record_event(client: kube::Client, target: Target, health: Health) {
+    let event_reporter = events::Reporter {
+        controller: CONTROLLER_NAME.to_string(),
+        instance: None,
+    };
+    let service = ObjectRef::<Service>::new(&target.service).within(&target.namespace);
+    let event_recorder = events::Recorder::new(client, event_reporter, service.into());
+
+    let (type_, reason) = if health.ready {
+        (events::EventType::Normal, PROBE_SUCCEEDED)
+    } else {
+        (events::EventType::Warning, PROBE_FAILED)
+    };
+    if let Err(error) = event_recorder
+        .publish(events::Event {
+            type_,
+            reason: reason.to_string(),
+            note: Some(health.detail),
+            action: "Probe".to_string(),
+            secondary: None,
+        })
+        .await
+    {
+        tracing::error!(%error, "failed to record event");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{backend, init_tracing, traffic_split};
+    use tokio::net::TcpListener;
+
+    /// Given a probed service, it becomes unhealthy only after the failure threshold of consecutive
+    /// failed probes, and healthy again only after the success threshold of consecutive successful
+    /// ones.
+    #[test]
+    fn probes_fail_over_after_consecutive_failures() {
+        let _log = init_tracing();
+        let prober = Prober::from_config(Config {
+            interval: time::Duration::from_secs(10),
+            timeout: time::Duration::from_secs(2),
+            success_threshold: 2,
+            failure_threshold: 3,
+        });
+        let ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![backend("primary", 1), backend("secondary", 0)],
+        );
+        let fail = || Err("GET http://10.0.0.1:80/ready returned HTTP 503".to_string());
+        let succeed = || Ok("GET http://10.0.0.1:80/ready returned HTTP 200".to_string());
+
+        // Unprobed services are healthy.
+        assert_eq!(prober.health(&ts, "primary"), Health::ready(""));
+
+        assert_eq!(prober.observe("default", "primary", fail()), None);
+        assert_eq!(prober.observe("default", "primary", fail()), None);
+        assert!(prober.health(&ts, "primary").ready);
+        // Only the transition is reported, so that it can be recorded as an event.
+        let health = prober
+            .observe("default", "primary", fail())
+            .expect("health must change");
+        assert_eq!(health, prober.health(&ts, "primary"));
+        assert!(!health.ready);
+        assert!(
+            health.detail.contains("3 consecutive failures"),
+            "{:?}",
+            health
+        );
+
+        // A single success does not reach the success threshold, and a failure resets it.
+        prober.observe("default", "primary", succeed());
+        assert!(!prober.health(&ts, "primary").ready);
+        prober.observe("default", "primary", fail());
+        prober.observe("default", "primary", succeed());
+        assert!(!prober.health(&ts, "primary").ready);
+        assert!(prober.observe("default", "primary", succeed()).is_some());
+        assert!(prober.health(&ts, "primary").ready);
+    }
+
+    /// Given encoded gRPC health check responses, their serving status is decoded, and truncated
+    /// responses are rejected.
+    #[test]
+    fn decodes_grpc_health_responses() {
+        // A `HealthCheckResponse` with `status: NOT_SERVING`
+        assert_eq!(decode_health_response(&[0, 0, 0, 0, 2, 0x08, 0x02]), Ok(2));
+        // Unknown fields are skipped, and an omitted status is `UNKNOWN`.
+        assert_eq!(
+            decode_health_response(&[0, 0, 0, 0, 3, 0x12, 0x01, 0x61]),
+            Ok(0)
+        );
+        assert!(decode_health_response(&[0, 0, 0, 0, 2, 0x08]).is_err());
+        assert!(decode_health_response(&[]).is_err());
+    }
+
+    /// Given services annotated for probing, probes target their cluster IP and port, with IPv6
+    /// addresses bracketed in URIs.
+    #[test]
+    fn probes_target_cluster_ips() {
+        let svc = |ip: &str| {
+            let mut svc = k8s_openapi::api::core::v1::Service::default();
+            svc.metadata.name = Some("primary".to_owned());
+            svc.metadata.namespace = Some("default".to_owned());
+            svc.annotations_mut()
+                .insert(PROBE_PATH_ANNOTATION.to_owned(), "/ready".to_owned());
+            svc.spec = Some(k8s_openapi::api::core::v1::ServiceSpec {
+                cluster_ip: Some(ip.to_owned()),
+                ports: Some(vec![k8s_openapi::api::core::v1::ServicePort {
+                    port: 8080,
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            });
+            svc
+        };
+        let addr = |ip: &str| target(&svc(ip)).map(|t| t.addr.to_string());
+
+        assert_eq!(addr("10.0.0.1"), Some("10.0.0.1:8080".to_owned()));
+        assert_eq!(addr("fd00::1"), Some("[fd00::1]:8080".to_owned()));
+        // Headless services have no cluster IP to probe.
+        assert_eq!(addr("None"), None);
+    }
+
+    /// Given a TCP probe target, the probe succeeds while a listener accepts connections on its
+    /// address and fails once it is closed.
+    #[tokio::test]
+    async fn probes_tcp_targets() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = Target {
+            namespace: "default".to_owned(),
+            service: "primary".to_owned(),
+            addr: listener.local_addr().unwrap(),
+            kind: ProbeKind::Tcp,
+        };
+        let clients = Clients {
+            http: hyper::Client::new(),
+            grpc: hyper::Client::builder().http2_only(true).build_http(),
+        };
+        let timeout = Duration::from_secs(1);
+
+        assert_eq!(
+            probe(&clients, &target, timeout).await,
+            Ok(format!("connected to {}", target.addr))
+        );
+
+        drop(listener);
+        let result = probe(&clients, &target, timeout).await;
+        assert!(
+            matches!(&result, Err(e) if e.starts_with(&format!("connecting to {} failed", target.addr))),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,433 @@
+//! Reports backends as unhealthy when Linkerd's proxy metrics, as queried from a Prometheus API,
+//! show a sustained drop in success rate or rise in latency.
+//!
+//! Splits opt in by setting a [`MIN_SUCCESS_RATE_ANNOTATION`] and/or a [`MAX_LATENCY_ANNOTATION`].
+//! A backend is unhealthy once every sample taken over the split's failure window breaches a
+//! threshold. Samples are only taken while a backend receives traffic, so once traffic moves off
+//! of an unhealthy backend it is retried after another window elapses.
+
+use crate::{Health, HealthSource, TrafficSplit};
+use anyhow::{bail, Context, Result};
+use futures::prelude::*;
+use hyper::{client::HttpConnector, header, Body, Request, Uri};
+use kube::ResourceExt;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::time::{self, Duration, Instant};
+
+/// The minimum fraction of successful responses, e.g. `0.99`, below which a backend is unhealthy
+pub const MIN_SUCCESS_RATE_ANNOTATION: &str = "failover.linkerd.io/min-success-rate";
+
+/// The maximum p99 response latency, e.g. `500ms`, above which a backend is unhealthy
+pub const MAX_LATENCY_ANNOTATION: &str = "failover.linkerd.io/max-latency";
+
+/// How long thresholds must be breached before a backend is unhealthy, e.g. `1m`
+pub const FAILURE_WINDOW_ANNOTATION: &str = "failover.linkerd.io/failure-window";
+
+/// The range over which each sample's rates are computed
+const RATE_WINDOW: &str = "1m";
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+const SUCCESS_RATE_QUERY: &str = r#"sum by (dst_namespace, dst_service) (rate(response_total{direction="outbound", classification="success"}[RATE_WINDOW])) / sum by (dst_namespace, dst_service) (rate(response_total{direction="outbound"}[RATE_WINDOW]))"#;
+
+const LATENCY_QUERY: &str = r#"histogram_quantile(0.99, sum by (le, dst_namespace, dst_service) (rate(response_latency_ms_bucket{direction="outbound"}[RATE_WINDOW])))"#;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The base URL of the Prometheus API, e.g. `http://prometheus.linkerd-viz.svc.cluster.local:9090`
+    pub url: Uri,
+    /// How often metrics are queried
+    pub interval: Duration,
+    /// The failure window of splits that do not set one
+    pub default_window: Duration,
+    /// The longest failure window a split may set
+    pub max_window: Duration,
+}
+
+/// A health source backed by samples of each service's success rate and latency
+#[derive(Clone)]
+pub struct PrometheusHealth {
+    samples: Arc<Mutex<Samples>>,
+    interval: Duration,
+    default_window: Duration,
+    max_window: Duration,
+}
+
+type Samples = HashMap<(String, String), VecDeque<Sample>>;
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    at: Instant,
+    success_rate: Option<f64>,
+    latency: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Thresholds {
+    min_success_rate: Option<f64>,
+    max_latency: Option<Duration>,
+}
+
+impl PrometheusHealth {
+    /// Returns the health source along with a future that polls the Prometheus API, which must be
+    /// spawned for the source to observe any metrics.
+    pub fn new(config: Config) -> Result<(Self, impl Future<Output = ()>)> {
+        if config.url.scheme_str() != Some("http") {
+            bail!("the Prometheus URL must use http: {}", config.url);
+        }
+        let health = Self {
+            samples: Default::default(),
+            interval: config.interval,
+            default_window: config.default_window,
+            max_window: config.max_window,
+        };
+        Ok((health.clone(), poll(config, health)))
+    }
+
+    /// Records a service's metrics at the current time. Unknown values, e.g. because the service
+    /// received no traffic, never breach thresholds.
+    fn record(
+        &self,
+        namespace: &str,
+        service: &str,
+        success_rate: Option<f64>,
+        latency: Option<Duration>,
+    ) {
+        let sample = Sample {
+            at: Instant::now(),
+            success_rate,
+            latency,
+        };
+        tracing::trace!(%namespace, %service, %sample);
+        let mut samples = self.samples.lock().expect("samples lock poisoned");
+        samples
+            .entry((namespace.to_string(), service.to_string()))
+            .or_default()
+            .push_back(sample);
+    }
+
+    /// Drops samples that are too old to affect any split's health: those older than the longest
+    /// window and the retry window after it
+    fn prune(&self) {
+        let now = Instant::now();
+        let retention = self.max_window * 2;
+        let mut samples = self.samples.lock().expect("samples lock poisoned");
+        samples.retain(|_, samples| {
+            while samples.front().map_or(false, |s| now - s.at > retention) {
+                samples.pop_front();
+            }
+            !samples.is_empty()
+        });
+    }
+
+    /// Returns the split's failure window, which is at least the polling interval: a shorter window
+    /// would treat a single breaching sample as sustained and retry before the next sample.
+    fn window(&self, split: &TrafficSplit) -> Duration {
+        let window = split
+            .annotations()
+            .get(FAILURE_WINDOW_ANNOTATION)
+            .and_then(|w| match humantime::parse_duration(w) {
+                Ok(window) => Some(window),
+                Err(error) => {
+                    tracing::warn!(%error, window = %w, "ignoring invalid failure window");
+                    None
+                }
+            })
+            .unwrap_or(self.default_window);
+        if window < self.interval {
+            tracing::warn!(
+                window = %humantime::format_duration(window),
+                interval = %humantime::format_duration(self.interval),
+                "failure window is shorter than the metrics interval; using the interval",
+            );
+        }
+        window.min(self.max_window).max(self.interval)
+    }
+}
+
+impl HealthSource for PrometheusHealth {
+    fn health(&self, split: &TrafficSplit, service: &str) -> Health {
+        let thresholds = match Thresholds::from_split(split) {
+            Some(thresholds) => thresholds,
+            None => return Health::ready(""),
+        };
+        let window = self.window(split);
+        let namespace = split.namespace().unwrap_or_default();
+
+        let samples = self.samples.lock().expect("samples lock poisoned");
+        let samples = match samples.get(&(namespace, service.to_string())) {
+            Some(samples) if !samples.is_empty() => samples,
+            _ => return Health::ready("no traffic observed"),
+        };
+        let latest = samples.back().expect("samples must not be empty");
+        if !thresholds.breached(latest) {
+            return Health::ready(latest.to_string());
+        }
+
+        // Find when the current run of breaching samples began.
+        let since = samples
+            .iter()
+            .rev()
+            .take_while(|s| thresholds.breached(s))
+            .last()
+            .map_or(latest.at, |s| s.at);
+        let sustained = latest.at - since >= window;
+        // Without new samples, e.g. because traffic moved to other backends, the backend is
+        // retried once a window has passed.
+        let stale = Instant::now() - latest.at >= window;
+        let detail = format!(
+            "{latest}, breaching thresholds for {}",
+            humantime::format_duration(truncate(latest.at - since))
+        );
+        if sustained && !stale {
+            Health::not_ready(detail)
+        } else {
+            Health::ready(detail)
+        }
+    }
+}
+
+impl Thresholds {
+    /// Reads the split's thresholds, returning `None` if it sets none
+    fn from_split(split: &TrafficSplit) -> Option<Self> {
+        let annotations = split.annotations();
+        let min_success_rate = annotations
+            .get(MIN_SUCCESS_RATE_ANNOTATION)
+            .and_then(|sr| match sr.parse::<f64>() {
+                Ok(sr) if (0.0..=1.0).contains(&sr) => Some(sr),
+                _ => {
+                    tracing::warn!(success_rate = %sr, "ignoring invalid minimum success rate");
+                    None
+                }
+            });
+        let max_latency = annotations.get(MAX_LATENCY_ANNOTATION).and_then(|l| {
+            match humantime::parse_duration(l) {
+                Ok(latency) => Some(latency),
+                Err(error) => {
+                    tracing::warn!(%error, latency = %l, "ignoring invalid maximum latency");
+                    None
+                }
+            }
+        });
+        if min_success_rate.is_none() && max_latency.is_none() {
+            return None;
+        }
+        Some(Self {
+            min_success_rate,
+            max_latency,
+        })
+    }
+
+    fn breached(&self, sample: &Sample) -> bool {
+        let success_rate = matches!(
+            (self.min_success_rate, sample.success_rate),
+            (Some(min), Some(sr)) if sr < min
+        );
+        let latency = matches!(
+            (self.max_latency, sample.latency),
+            (Some(max), Some(l)) if l > max
+        );
+        success_rate || latency
+    }
+}
+
+impl std::fmt::Display for Sample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.success_rate {
+            Some(sr) => write!(f, "success rate {:.2}%", sr * 100.0)?,
+            None => write!(f, "success rate unknown")?,
+        }
+        match self.latency {
+            Some(l) => write!(f, ", p99 latency {}ms", l.as_millis()),
+            None => write!(f, ", p99 latency unknown"),
+        }
+    }
+}
+
+/// Queries the Prometheus API every interval, recording a sample for each service that received
+/// traffic
+async fn poll(config: Config, health: PrometheusHealth) {
+    let client = hyper::Client::new();
+    let mut interval = time::interval(config.interval);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+
+        let queried = async {
+            let success_rates = query(&client, &config.url, SUCCESS_RATE_QUERY).await?;
+            let latencies = query(&client, &config.url, LATENCY_QUERY).await?;
+            Ok::<_, anyhow::Error>((success_rates, latencies))
+        };
+        let (success_rates, latencies) = match queried.await {
+            Ok(results) => results,
+            Err(error) => {
+                tracing::warn!(error = %format!("{error:#}"), "failed to query metrics");
+                continue;
+            }
+        };
+
+        let services = success_rates
+            .keys()
+            .chain(latencies.keys())
+            .collect::<HashSet<_>>();
+        for key @ (namespace, service) in services {
+            let latency = latencies
+                .get(key)
+                .map(|ms| Duration::from_secs_f64(ms / 1000.0));
+            health.record(namespace, service, success_rates.get(key).copied(), latency);
+        }
+        health.prune();
+    }
+}
+
+/// Runs an instant query, returning its finite values by namespace and service
+async fn query(
+    client: &hyper::Client<HttpConnector>,
+    url: &Uri,
+    query: &str,
+) -> Result<HashMap<(String, String), f64>> {
+    #[derive(serde::Deserialize)]
+    struct Response {
+        data: Data,
+    }
+    #[derive(serde::Deserialize)]
+    struct Data {
+        result: Vec<Series>,
+    }
+    #[derive(serde::Deserialize)]
+    struct Series {
+        metric: HashMap<String, String>,
+        value: (f64, String),
+    }
+
+    let uri = format!("{}/api/v1/query", url.to_string().trim_end_matches('/'));
+    let body = form_urlencoded::Serializer::new(String::new())
+        .append_pair("query", &query.replace("RATE_WINDOW", RATE_WINDOW))
+        .finish();
+    let req = Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body))?;
+
+    let rsp = time::timeout(QUERY_TIMEOUT, client.request(req))
+        .await
+        .context("query timed out")??;
+    if !rsp.status().is_success() {
+        bail!("query failed with HTTP {}", rsp.status());
+    }
+    let body = hyper::body::to_bytes(rsp.into_body()).await?;
+    let rsp: Response = serde_json::from_slice(&body).context("invalid query response")?;
+
+    Ok(rsp
+        .data
+        .result
+        .into_iter()
+        .filter_map(|series| {
+            let value = series
+                .value
+                .1
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())?;
+            let namespace = series.metric.get("dst_namespace")?.clone();
+            let service = series.metric.get("dst_service")?.clone();
+            Some(((namespace, service), value))
+        })
+        .collect())
+}
+
+/// Drops sub-second precision for display
+fn truncate(d: Duration) -> Duration {
+    Duration::from_secs(d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{backend, init_tracing, traffic_split};
+
+    /// A backend is unhealthy once its metrics breach the split's thresholds for its whole failure
+    /// window. Once samples stop, e.g. because traffic moved off of it, the backend is retried
+    /// after another window.
+    #[tokio::test(start_paused = true)]
+    async fn metrics_fail_over_after_sustained_breach() {
+        let _log = init_tracing();
+        let (health, _poll) = PrometheusHealth::new(Config {
+            url: "http://prometheus.test:9090".parse().unwrap(),
+            interval: time::Duration::from_secs(10),
+            default_window: time::Duration::from_secs(60),
+            max_window: time::Duration::from_secs(600),
+        })
+        .unwrap();
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![backend("primary", 1), backend("secondary", 0)],
+        );
+        ts.annotations_mut()
+            .insert(MIN_SUCCESS_RATE_ANNOTATION.to_owned(), "0.9".to_owned());
+        ts.annotations_mut()
+            .insert(FAILURE_WINDOW_ANNOTATION.to_owned(), "30s".to_owned());
+        let step = time::Duration::from_secs(15);
+
+        health.record("default", "primary", Some(0.5), None);
+        assert!(health.health(&ts, "primary").ready);
+        time::advance(step).await;
+        health.record("default", "primary", Some(0.5), None);
+        assert!(health.health(&ts, "primary").ready);
+        time::advance(step).await;
+        health.record("default", "primary", Some(0.5), None);
+        assert!(!health.health(&ts, "primary").ready);
+
+        // Without samples for a window, the backend is retried.
+        time::advance(time::Duration::from_secs(30)).await;
+        assert!(health.health(&ts, "primary").ready);
+
+        // A failed retry immediately fails over again, while a successful one recovers.
+        health.record("default", "primary", Some(0.5), None);
+        assert!(!health.health(&ts, "primary").ready);
+        time::advance(step).await;
+        health.record("default", "primary", Some(0.99), None);
+        assert!(health.health(&ts, "primary").ready);
+
+        // Splits without thresholds ignore metrics, as do backends without traffic.
+        ts.annotations_mut().remove(MIN_SUCCESS_RATE_ANNOTATION);
+        assert!(health.health(&ts, "primary").ready);
+        assert!(health.health(&ts, "secondary").ready);
+    }
+
+    /// A failure window shorter than the metrics interval is extended to the interval, so a single
+    /// breaching sample doesn't fail over and a failed-over backend isn't retried between samples.
+    #[tokio::test(start_paused = true)]
+    async fn metrics_failure_window_is_at_least_the_interval() {
+        let _log = init_tracing();
+        let (health, _poll) = PrometheusHealth::new(Config {
+            url: "http://prometheus.test:9090".parse().unwrap(),
+            interval: time::Duration::from_secs(10),
+            default_window: time::Duration::from_secs(60),
+            max_window: time::Duration::from_secs(600),
+        })
+        .unwrap();
+        let mut ts = traffic_split(
+            "ts0",
+            "primary",
+            vec![backend("primary", 1), backend("secondary", 0)],
+        );
+        ts.annotations_mut()
+            .insert(MIN_SUCCESS_RATE_ANNOTATION.to_owned(), "0.9".to_owned());
+        ts.annotations_mut()
+            .insert(FAILURE_WINDOW_ANNOTATION.to_owned(), "1s".to_owned());
+
+        health.record("default", "primary", Some(0.5), None);
+        assert!(health.health(&ts, "primary").ready);
+        time::advance(time::Duration::from_secs(10)).await;
+        health.record("default", "primary", Some(0.5), None);
+        assert!(!health.health(&ts, "primary").ready);
+        time::advance(time::Duration::from_secs(5)).await;
+        assert!(!health.health(&ts, "primary").ready);
+        time::advance(time::Duration::from_secs(5)).await;
+        assert!(health.health(&ts, "primary").ready);
+    }
+}
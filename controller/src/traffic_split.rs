@@ -1,15 +1,72 @@
-use super::Ctx;
+use super::{locality, Ctx, Health, HealthSource, Hook};
 use futures::prelude::*;
 use kube::{
     api::{Api, Patch, PatchParams},
     runtime::{events, reflector::ObjectRef, watcher::Event},
     ResourceExt,
 };
+use std::{collections::HashSet, fmt, str::FromStr, sync::Arc};
 use tokio::{sync::mpsc, time};
 
-const FAILOVER: &str = "Failover";
+/// The reason and action of the events recorded when a split's weights are changed
+pub const FAILOVER: &str = "Failover";
 const CONTROLLER_NAME: &str = "linkerd-failover";
 
+/// The reason of the warning events recorded when another failover controller manages a split
+pub const COMPETING_CONTROLLER: &str = "CompetingController";
+
+/// The reason of the warning events recorded when a split's primary and all of its fallbacks become
+/// unavailable
+pub const ALL_BACKENDS_UNAVAILABLE: &str = "AllBackendsUnavailable";
+
+/// The prefix of failover controllers' field managers, by which other failover controllers are
+/// detected. Earlier versions patched traffic splits with this field manager itself, so it is never
+/// reported as a competing controller.
+pub const DEFAULT_FIELD_MANAGER: &str = "failover.linkerd.io";
+
+/// Names the backend that should receive traffic while it is ready
+pub const PRIMARY_SERVICE_ANNOTATION: &str = "failover.linkerd.io/primary-service";
+
+/// Overrides the controller's readiness-based decision. The value is either the name of a backend
+/// that should receive all traffic or [`OVERRIDE_FALLBACKS`].
+pub const OVERRIDE_ANNOTATION: &str = "failover.linkerd.io/override";
+
+/// An [`OVERRIDE_ANNOTATION`] value that moves traffic off of the primary and onto all ready
+/// fallbacks
+pub const OVERRIDE_FALLBACKS: &str = "*";
+
+/// Determines the weights of a split's fallbacks while it is failed over: [`EQUAL`], the default,
+/// or [`CAPACITY`]
+pub const FALLBACK_WEIGHTS_ANNOTATION: &str = "failover.linkerd.io/fallback-weights";
+
+/// A [`FALLBACK_WEIGHTS_ANNOTATION`] value that gives every active fallback the same weight
+pub const EQUAL: &str = "equal";
+
+/// A [`FALLBACK_WEIGHTS_ANNOTATION`] value that weighs active fallbacks by their number of ready
+/// addresses
+pub const CAPACITY: &str = "capacity";
+
+/// Determines how a split fails over: [`FULL`], the default, or [`PARTIAL`]
+pub const FAILOVER_MODE_ANNOTATION: &str = "failover.linkerd.io/failover-mode";
+
+/// A [`FAILOVER_MODE_ANNOTATION`] value that keeps all traffic on the primary while it is ready
+pub const FULL: &str = "full";
+
+/// A [`FAILOVER_MODE_ANNOTATION`] value that shifts a share of traffic to the fallbacks while some
+/// of the primary's addresses are not ready
+pub const PARTIAL: &str = "partial";
+
+/// The sum of a split's weights while its primary is partially failed over
+pub const PARTIAL_SCALE: u32 = 100;
+
+/// Determines a split's weights while its primary and all of its fallbacks are unavailable, as an
+/// [`AllUnavailable`] value. Splits without the annotation use the controller's default.
+pub const ALL_UNAVAILABLE_ANNOTATION: &str = "failover.linkerd.io/all-unavailable";
+
+/// Names the failover group of a split. Splits in the same namespace and group leave their
+/// primaries together and fail over to the same tier of fallbacks.
+pub const GROUP_ANNOTATION: &str = "failover.linkerd.io/group";
+
 /// The `split.smi-spec.io/TrafficSplit` custom resource
 #[derive(
     Clone,
@@ -28,6 +85,9 @@ const CONTROLLER_NAME: &str = "linkerd-failover";
     namespaced
 )]
 pub struct TrafficSplitSpec {
+    /// The apex service that clients send requests to
+    #[serde(default)]
+    pub service: String,
     pub backends: Vec<Backend>,
 }
 
@@ -40,22 +100,149 @@ pub struct Backend {
     pub weight: u32,
 }
 
+/// How a split is weighted while its primary and all of its fallbacks are unavailable
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AllUnavailable {
+    /// Sets every backend's weight to 0
+    #[default]
+    Zero,
+    /// Keeps the split's last weights
+    Keep,
+    /// Sends all traffic to the primary, even though it isn't ready
+    Primary,
+}
+
+impl AllUnavailable {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Zero => "zero",
+            Self::Keep => "keep",
+            Self::Primary => "primary",
+        }
+    }
+}
+
+impl FromStr for AllUnavailable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "zero" => Ok(Self::Zero),
+            "keep" => Ok(Self::Keep),
+            "primary" => Ok(Self::Primary),
+            _ => anyhow::bail!("invalid value {s:?}; expected zero, keep or primary"),
+        }
+    }
+}
+
+impl fmt::Display for AllUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The backend that receives traffic while it is ready, as resolved by [`primary_service`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Primary<'a> {
+    pub service: &'a str,
+    pub source: PrimarySource,
+}
+
+impl Primary<'_> {
+    /// Returns true if the primary was not explicitly named by the split
+    pub fn is_implicit(&self) -> bool {
+        self.source == PrimarySource::FirstBackend
+    }
+}
+
+/// Describes how a [`TrafficSplit`]'s [`Primary`] was chosen
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrimarySource {
+    /// The primary is named by the [`PRIMARY_SERVICE_ANNOTATION`]
+    Annotation,
+    /// The split has no primary annotation, so its first backend is used
+    FirstBackend,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct FailoverUpdate {
     pub target: ObjectRef<TrafficSplit>,
     pub backends: Vec<Backend>,
     pub primary_active: bool,
+    /// Field managers of other failover controllers that last updated the split's backends
+    pub competing_managers: Vec<String>,
+    /// Set when the split's primary and all of its fallbacks have just become unavailable, to how
+    /// the split is weighted until one of them is available again
+    pub all_unavailable: Option<AllUnavailable>,
+}
+
+impl FailoverUpdate {
+    /// Describes the update in the event recorded for it
+    pub fn event_note(&self) -> String {
+        // Fallbacks only share traffic with an active primary while it is degraded.
+        let shared = self.backends.iter().filter(|b| b.weight > 0).count() > 1;
+        if self.primary_active && shared {
+            format!(
+                "trafficsplit/{} shifting traffic from degraded primary to fallbacks",
+                self.target.name
+            )
+        } else if self.primary_active {
+            format!(
+                "trafficsplit/{} switching traffic to primary",
+                self.target.name
+            )
+        } else {
+            format!(
+                "trafficsplit/{} failing over to fallbacks",
+                self.target.name
+            )
+        }
+    }
+
+    /// Describes the warning event recorded when all of the split's backends have just become
+    /// unavailable, if they have
+    pub fn all_unavailable_note(&self) -> Option<String> {
+        let action = match self.all_unavailable? {
+            AllUnavailable::Zero => "removing all traffic",
+            AllUnavailable::Keep => "keeping its last weights",
+            AllUnavailable::Primary => "sending all traffic to primary",
+        };
+        Some(format!(
+            "trafficsplit/{} has no available backends; {action}",
+            self.target.name
+        ))
+    }
+
+    /// Describes the competing failover controllers in the warning event recorded for them, if any
+    pub fn competing_managers_note(&self) -> Option<String> {
+        if self.competing_managers.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "trafficsplit/{} backends were last updated by {}; multiple failover controllers may select it",
+            self.target.name,
+            self.competing_managers.join(", ")
+        ))
+    }
 }
 
-/// Reads from `patches` and patches traffic split resources.
+/// Reads from `patches` and patches traffic split resources, notifying `hooks` of each split that
+/// is patched.
 pub async fn apply_patches(
     mut patches: mpsc::Receiver<FailoverUpdate>,
     client: kube::Client,
+    field_manager: &str,
     timeout: time::Duration,
+    hooks: &[Arc<dyn Hook>],
 ) {
-    let params = PatchParams::apply("failover.linkerd.io");
+    let params = PatchParams::apply(field_manager);
     while let Some(p) = patches.recv().await {
-        patch(client.clone(), &params, timeout, p).await;
+        if patch(client.clone(), &params, timeout, &p).await {
+            for hook in hooks {
+                hook.patched(&p);
+            }
+        }
     }
     tracing::debug!("patch stream ended");
 }
@@ -70,137 +257,705 @@ where
     }
 }
 
-pub(super) async fn handle(ev: Event<TrafficSplit>, ctx: &Ctx) {
+/// Handles a single traffic split watch event. Exposed so that the controller's decisions can be
+/// replayed against in-memory stores.
+pub async fn handle(ev: Event<TrafficSplit>, ctx: &Ctx) {
     match ev {
-        Event::Restarted(tss) => {
-            for ts in &tss {
-                update(ObjectRef::from_obj(ts), ctx).await;
-            }
+        Event::Restarted(_) => {
+            update_all(ctx).await;
         }
         Event::Applied(ts) => {
             update(ObjectRef::from_obj(&ts), ctx).await;
         }
-        Event::Deleted(_) => {
-            // nothing to do when a split is deleted.
+        Event::Deleted(ts) => {
+            ctx.unavailable
+                .lock()
+                .expect("unavailable splits lock poisoned")
+                .remove(&ObjectRef::from_obj(&ts));
         }
     }
 }
 
-/// Processes a traffic split update for the rereferenced resource. If a write is necessary, a patch
-/// is enqueued via the context.
+/// Re-evaluates all cached traffic splits, so that changes reported by health sources take effect
+/// even when no watch event occurs.
+pub(super) async fn resync(ctx: &Ctx) {
+    tracing::debug!("resyncing traffic splits");
+    update_all(ctx).await;
+}
+
+/// Processes a traffic split update for the rereferenced resource.
+pub(super) async fn update(target: ObjectRef<TrafficSplit>, ctx: &Ctx) {
+    update_each(Some(target), ctx).await;
+}
+
+/// Evaluates each cached traffic split once
+pub(super) async fn update_all(ctx: &Ctx) {
+    let targets = ctx
+        .traffic_splits
+        .state()
+        .iter()
+        .map(|ts| ObjectRef::from_obj(&**ts))
+        .collect::<Vec<_>>();
+    update_each(targets, ctx).await;
+}
+
+/// Processes updates for the referenced resources. Since a change to one split may move its whole
+/// failover group, every member of each split's group is evaluated, but only once.
+pub(super) async fn update_each(
+    targets: impl IntoIterator<Item = ObjectRef<TrafficSplit>>,
+    ctx: &Ctx,
+) {
+    let splits = ctx.traffic_splits.state();
+    let mut evaluated = HashSet::new();
+    for target in targets {
+        let split = match ctx.traffic_splits.get(&target) {
+            Some(s) => s,
+            None => {
+                tracing::warn!(namespace = ?target.namespace, trafficsplit = %target.name, "trafficsplit not found");
+                continue;
+            }
+        };
+
+        let members = group_members(&split, splits.iter().map(|s| &**s));
+        for member in &members {
+            if evaluated.insert(ObjectRef::from_obj(*member)) {
+                evaluate(member, &members, ctx).await;
+            }
+        }
+    }
+}
+
+/// Decides the split's weights, given the members of its failover group. If a write is necessary,
+/// a patch is enqueued via the context.
 #[tracing::instrument(skip_all, fields(
-    namespace = %target.namespace.as_ref().unwrap(),
-    trafficsplit = %target.name
+    namespace = %split.namespace().unwrap_or_default(),
+    trafficsplit = %split.name_any()
 ))]
-pub(super) async fn update(target: ObjectRef<TrafficSplit>, ctx: &Ctx) {
-    let namespace = target
-        .namespace
-        .as_ref()
-        .expect("trafficsplit must be namespaced");
+async fn evaluate(split: &TrafficSplit, members: &[&TrafficSplit], ctx: &Ctx) {
     tracing::debug!("checking traffic split for update");
 
-    let split = match ctx.traffic_splits.get(&target) {
-        Some(s) => s,
+    let decision = match decide_group(split, members, &*ctx.health) {
+        Some(decision) => decision,
         None => {
-            tracing::warn!("trafficsplit not found");
+            tracing::info!("trafficsplit has no backends; skipping");
             return;
         }
     };
 
-    let primary_service = match split
-        .annotations()
-        .get("failover.linkerd.io/primary-service")
-        .or_else(|| split.spec.backends.first().map(|backend| &backend.service))
-    {
-        Some(name) => name,
-        None => {
-            tracing::info!("trafficsplit has no backends; skipping");
-            return;
+    // A warning is recorded when the split's backends first become unavailable, even if its
+    // weights are kept.
+    let target = ObjectRef::from_obj(split);
+    let mut all_unavailable = None;
+    let decision = if decision.all_unavailable {
+        let policy = all_unavailable_policy(split, ctx.all_unavailable);
+        if ctx
+            .unavailable
+            .lock()
+            .expect("unavailable splits lock poisoned")
+            .insert(target.clone())
+        {
+            tracing::warn!(%policy, "all backends are unavailable");
+            all_unavailable = Some(policy);
         }
+        apply_all_unavailable(split, decision, policy)
+    } else {
+        ctx.unavailable
+            .lock()
+            .expect("unavailable splits lock poisoned")
+            .remove(&target);
+        decision
     };
-    let primary_active = ctx.endpoints_ready(namespace, primary_service);
+    let Decision {
+        primary_active,
+        backends,
+        health,
+        ..
+    } = decision;
 
-    let mut backends = Vec::with_capacity(split.spec.backends.len());
     let mut changed = false;
-    for backend in &split.spec.backends {
-        // If the primary service is active, *only* the primary service is active.
-        let active = if primary_active {
-            backend.service == *primary_service
-        } else {
-            // Otherwise, if the service has ready endpoints, it's active.
-            ctx.endpoints_ready(namespace, &backend.service)
-        };
-
-        let weight = if active { 1 } else { 0 };
-        if weight != backend.weight {
+    for ((backend, current), health) in backends.iter().zip(&split.spec.backends).zip(&health) {
+        if backend.weight != current.weight {
             changed = true;
             tracing::debug!(
                 service = %backend.service,
-                %weight,
+                weight = %backend.weight,
+                ready = %health.ready,
+                health = %health.detail,
                 "updating service weight"
             );
         } else {
             tracing::trace!(
                 service = %backend.service,
-                %weight,
+                weight = %backend.weight,
+                ready = %health.ready,
+                health = %health.detail,
                 "unchanged service weight"
             );
         }
-
-        backends.push({
-            let mut b = backend.clone();
-            b.weight = weight;
-            b
-        });
     }
 
-    if !changed {
+    if !changed && all_unavailable.is_none() {
         tracing::debug!("no update necessary");
         return;
     }
 
+    // If another failover controller last set the weights, the controllers are likely fighting
+    // over this split.
+    let competing_managers = competing_managers(split, &ctx.field_manager);
+    for manager in &competing_managers {
+        tracing::warn!(%manager, "trafficsplit backends were last updated by another failover controller");
+    }
+
     let update = FailoverUpdate {
         target,
         backends,
         primary_active,
+        competing_managers,
+        all_unavailable,
     };
     if ctx.patches.send(update).await.is_err() {
         tracing::error!("dropping update because the channel is closed");
     }
 }
 
+/// Resolves the split's primary service from its [`PRIMARY_SERVICE_ANNOTATION`], falling back to
+/// its first backend. Returns `None` if the split has neither.
+pub fn primary_service(split: &TrafficSplit) -> Option<Primary<'_>> {
+    if let Some(service) = split.annotations().get(PRIMARY_SERVICE_ANNOTATION) {
+        return Some(Primary {
+            service,
+            source: PrimarySource::Annotation,
+        });
+    }
+
+    split.spec.backends.first().map(|backend| Primary {
+        service: &backend.service,
+        source: PrimarySource::FirstBackend,
+    })
+}
+
+/// The weights the controller assigns to a split's backends, as computed by [`decide`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Decision {
+    pub primary_active: bool,
+    /// The split's backends, in order, with updated weights
+    pub backends: Vec<Backend>,
+    /// The health of each of the split's backends, in order
+    pub health: Vec<Health>,
+    /// Whether the split's primary and all of its fallbacks are unavailable, in which case every
+    /// weight is 0 until [`apply_all_unavailable`] is applied
+    pub all_unavailable: bool,
+}
+
+/// Computes the weights the controller assigns to the split's backends, given the health of each
+/// backend. Returns `None` if the split has no primary service.
+///
+/// The decision depends only on the split and the reported health, so it can be evaluated outside
+/// of the controller.
+pub fn decide(split: &TrafficSplit, health: &dyn HealthSource) -> Option<Decision> {
+    let primary_service = primary_service(split)?.service;
+    let health = split
+        .spec
+        .backends
+        .iter()
+        .map(|b| health.health(split, &b.service))
+        .collect::<Vec<_>>();
+    let ready = |service: &str| {
+        split
+            .spec
+            .backends
+            .iter()
+            .zip(&health)
+            .any(|(b, h)| b.service == service && h.ready)
+    };
+
+    let evacuated = |service: &str| {
+        split
+            .spec
+            .backends
+            .iter()
+            .zip(&health)
+            .any(|(b, h)| b.service == service && h.evacuated)
+    };
+
+    let override_service = split
+        .annotations()
+        .get(OVERRIDE_ANNOTATION)
+        .map(String::as_str)
+        .filter(|service| {
+            let valid = *service == OVERRIDE_FALLBACKS
+                || split.spec.backends.iter().any(|b| b.service == *service);
+            if !valid {
+                tracing::warn!(%service, "ignoring override for a service that is not a backend");
+            }
+            valid
+        })
+        // Evacuation takes precedence over pinning traffic to a backend.
+        .filter(|service| {
+            let pinned_evacuated = *service != OVERRIDE_FALLBACKS && evacuated(service);
+            if pinned_evacuated {
+                tracing::info!(%service, "ignoring override for an evacuated service");
+            }
+            !pinned_evacuated
+        });
+    let primary_ready = ready(primary_service);
+
+    // Splits that prefer their nearest fallbacks only send traffic to the ready fallbacks that are
+    // closest to the controller's cluster.
+    let distance = |service: &str| {
+        split
+            .spec
+            .backends
+            .iter()
+            .zip(&health)
+            .find(|(b, _)| b.service == service)
+            .and_then(|(_, h)| h.distance)
+            .unwrap_or(locality::UNKNOWN)
+    };
+    let nearest = if locality::prefers_nearest(split) {
+        split
+            .spec
+            .backends
+            .iter()
+            .filter(|b| b.service != primary_service && ready(&b.service))
+            .map(|b| distance(&b.service))
+            .min()
+    } else {
+        None
+    };
+    let fallback_active = |service: &str| {
+        service != primary_service
+            && ready(service)
+            && nearest.map_or(true, |d| distance(service) == d)
+    };
+
+    let active = |service: &str| match override_service {
+        // Traffic is forced off of the primary, so only ready fallbacks are active.
+        Some(OVERRIDE_FALLBACKS) => fallback_active(service),
+        // Traffic is pinned to a single backend, regardless of its readiness.
+        Some(pinned) => service == pinned,
+        // If the primary service is active, *only* the primary service is active. Otherwise, if
+        // the service has ready endpoints, it's active.
+        None if primary_ready => service == primary_service,
+        None => fallback_active(service),
+    };
+
+    // Fallbacks that share traffic may be weighed by their capacity, while a single active
+    // backend always has a weight of 1.
+    let capacity = |service: &str| {
+        split
+            .spec
+            .backends
+            .iter()
+            .zip(&health)
+            .find(|(b, _)| b.service == service)
+            .and_then(|(_, h)| h.capacity)
+    };
+    let proportional = fallback_weights(split) == CAPACITY
+        && matches!(override_service, None | Some(OVERRIDE_FALLBACKS));
+    let fallback_weight = |service: &str| {
+        if proportional {
+            capacity(service).unwrap_or(1).max(1)
+        } else {
+            1
+        }
+    };
+    let weight = |service: &str| {
+        if !active(service) {
+            0
+        } else if fallback_active(service) {
+            fallback_weight(service)
+        } else {
+            1
+        }
+    };
+
+    // A degraded primary of a split that fails over partially keeps a share of the traffic in
+    // proportion to its ready addresses, and the active fallbacks share the remainder.
+    let primary_share = match override_service {
+        None if failover_mode(split) == PARTIAL => split
+            .spec
+            .backends
+            .iter()
+            .zip(&health)
+            .find(|(b, _)| b.service == primary_service)
+            .and_then(|(_, h)| degraded_share(h)),
+        _ => None,
+    };
+    let weights = match primary_share {
+        Some(share)
+            if split
+                .spec
+                .backends
+                .iter()
+                .any(|b| fallback_active(&b.service)) =>
+        {
+            scale_weights(split, primary_service, share, |service| {
+                fallback_active(service).then(|| fallback_weight(service))
+            })
+        }
+        _ => split
+            .spec
+            .backends
+            .iter()
+            .map(|b| weight(&b.service))
+            .collect(),
+    };
+
+    let primary_active = active(primary_service);
+    let backends = split
+        .spec
+        .backends
+        .iter()
+        .zip(weights)
+        .map(|(backend, weight)| {
+            let mut b = backend.clone();
+            b.weight = weight;
+            b
+        })
+        .collect();
+    // Overrides are explicit, so they are followed even if none of their backends are available.
+    let all_unavailable = override_service.is_none() && !health.iter().any(|h| h.ready);
+    Some(Decision {
+        primary_active,
+        backends,
+        health,
+        all_unavailable,
+    })
+}
+
+/// Returns how the split is weighted while all of its backends are unavailable, from its
+/// [`ALL_UNAVAILABLE_ANNOTATION`] or else `default`
+pub fn all_unavailable_policy(split: &TrafficSplit, default: AllUnavailable) -> AllUnavailable {
+    all_unavailable_annotation(split).unwrap_or(default)
+}
+
+/// Returns the split's [`ALL_UNAVAILABLE_ANNOTATION`], ignoring invalid values
+pub fn all_unavailable_annotation(split: &TrafficSplit) -> Option<AllUnavailable> {
+    let value = split.annotations().get(ALL_UNAVAILABLE_ANNOTATION)?;
+    match value.parse() {
+        Ok(policy) => Some(policy),
+        Err(error) => {
+            tracing::warn!(%error, "ignoring invalid all-unavailable annotation");
+            None
+        }
+    }
+}
+
+/// Applies `policy` to a decision in which all of the split's backends are unavailable. Other
+/// decisions are returned unchanged.
+pub fn apply_all_unavailable(
+    split: &TrafficSplit,
+    decision: Decision,
+    policy: AllUnavailable,
+) -> Decision {
+    if !decision.all_unavailable {
+        return decision;
+    }
+    let primary = primary_service(split).map(|p| p.service);
+    let backends = match policy {
+        AllUnavailable::Zero => decision.backends,
+        AllUnavailable::Keep => split.spec.backends.clone(),
+        AllUnavailable::Primary => decision
+            .backends
+            .into_iter()
+            .map(|mut b| {
+                b.weight = if Some(b.service.as_str()) == primary {
+                    1
+                } else {
+                    0
+                };
+                b
+            })
+            .collect(),
+    };
+    // Evacuated backends never receive traffic, whatever the policy.
+    let backends = backends
+        .into_iter()
+        .zip(&decision.health)
+        .map(|(mut b, h)| {
+            if h.evacuated {
+                b.weight = 0;
+            }
+            b
+        })
+        .collect::<Vec<_>>();
+    let primary_active = backends
+        .iter()
+        .any(|b| Some(b.service.as_str()) == primary && b.weight > 0);
+    Decision {
+        primary_active,
+        backends,
+        ..decision
+    }
+}
+
+/// Returns the share of [`PARTIAL_SCALE`] that a ready primary keeps, or `None` if all of its
+/// addresses are ready or they are unknown
+fn degraded_share(health: &Health) -> Option<u32> {
+    match (health.ready, health.capacity, health.addresses) {
+        (true, Some(ready), Some(addresses)) if ready < addresses => {
+            let share = u64::from(PARTIAL_SCALE) * u64::from(ready) / u64::from(addresses);
+            Some((share as u32).max(1))
+        }
+        _ => None,
+    }
+}
+
+/// Gives the primary `share` of [`PARTIAL_SCALE`] and spreads the remainder over the active
+/// fallbacks in proportion to their weights. Rounding leftovers go to the first fallbacks.
+fn scale_weights(
+    split: &TrafficSplit,
+    primary_service: &str,
+    share: u32,
+    fallback_weight: impl Fn(&str) -> Option<u32>,
+) -> Vec<u32> {
+    let fallbacks = split
+        .spec
+        .backends
+        .iter()
+        .map(|b| {
+            if b.service == primary_service {
+                None
+            } else {
+                fallback_weight(&b.service)
+            }
+        })
+        .collect::<Vec<_>>();
+    let total = fallbacks
+        .iter()
+        .flatten()
+        .map(|w| u64::from(*w))
+        .sum::<u64>();
+    let remainder = PARTIAL_SCALE - share;
+
+    let mut weights = split
+        .spec
+        .backends
+        .iter()
+        .zip(&fallbacks)
+        .map(|(b, fallback)| match fallback {
+            _ if b.service == primary_service => share,
+            Some(weight) => (u64::from(remainder) * u64::from(*weight) / total) as u32,
+            None => 0,
+        })
+        .collect::<Vec<_>>();
+    let mut leftover = PARTIAL_SCALE - weights.iter().sum::<u32>();
+    for (weight, fallback) in weights.iter_mut().zip(&fallbacks) {
+        if leftover > 0 && fallback.is_some() {
+            *weight += 1;
+            leftover -= 1;
+        }
+    }
+    weights
+}
+
+/// Returns the split's [`FAILOVER_MODE_ANNOTATION`], ignoring invalid values
+fn failover_mode(split: &TrafficSplit) -> &str {
+    match split
+        .annotations()
+        .get(FAILOVER_MODE_ANNOTATION)
+        .map(String::as_str)
+    {
+        Some(PARTIAL) => PARTIAL,
+        None | Some(FULL) => FULL,
+        Some(mode) => {
+            tracing::warn!(%mode, "ignoring invalid failover mode");
+            FULL
+        }
+    }
+}
+
+/// Returns the split's [`FALLBACK_WEIGHTS_ANNOTATION`], ignoring invalid values
+fn fallback_weights(split: &TrafficSplit) -> &str {
+    match split
+        .annotations()
+        .get(FALLBACK_WEIGHTS_ANNOTATION)
+        .map(String::as_str)
+    {
+        Some(CAPACITY) => CAPACITY,
+        None | Some(EQUAL) => EQUAL,
+        Some(weights) => {
+            tracing::warn!(%weights, "ignoring invalid fallback weights");
+            EQUAL
+        }
+    }
+}
+
+/// Returns the split's failover group, if it has one
+pub fn group(split: &TrafficSplit) -> Option<&str> {
+    split
+        .annotations()
+        .get(GROUP_ANNOTATION)
+        .map(String::as_str)
+        .filter(|g| !g.is_empty())
+}
+
+/// Returns the splits in the same failover group as `split`, including `split` itself. A split
+/// without a group is its only member.
+pub fn group_members<'a>(
+    split: &TrafficSplit,
+    splits: impl IntoIterator<Item = &'a TrafficSplit>,
+) -> Vec<&'a TrafficSplit> {
+    let namespace = split.namespace();
+    let name = split.name_any();
+    splits
+        .into_iter()
+        .filter(|s| {
+            s.namespace() == namespace
+                && match group(split) {
+                    Some(g) => group(s) == Some(g),
+                    None => s.name_any() == name,
+                }
+        })
+        .collect()
+}
+
+/// Computes the weights the controller assigns to a split in a failover group. While the primaries
+/// of all of the group's members are ready, this is the same as [`decide`]. Otherwise, every member
+/// sends all traffic to the first tier of fallbacks that is ready in all members, where a split's
+/// tiers are its non-primary backends in order.
+///
+/// Splits with an [`OVERRIDE_ANNOTATION`] are decided individually and do not move the rest of the
+/// group. If no tier is ready in all members, each split is decided individually.
+pub fn decide_group(
+    split: &TrafficSplit,
+    members: &[&TrafficSplit],
+    health: &dyn HealthSource,
+) -> Option<Decision> {
+    let decision = decide(split, health)?;
+    let has_override = |s: &TrafficSplit| s.annotations().contains_key(OVERRIDE_ANNOTATION);
+    let grouped = members
+        .iter()
+        .copied()
+        .filter(|s| !has_override(s))
+        .collect::<Vec<_>>();
+    if has_override(split) || grouped.len() < 2 {
+        return Some(decision);
+    }
+
+    let primary_ready =
+        |s: &TrafficSplit| primary_service(s).map_or(true, |p| health.health(s, p.service).ready);
+    if grouped.iter().all(|s| primary_ready(s)) {
+        return Some(decision);
+    }
+
+    let fallbacks = |s: &TrafficSplit| -> Vec<String> {
+        let primary = primary_service(s).map(|p| p.service.to_string());
+        s.spec
+            .backends
+            .iter()
+            .filter(|b| Some(&b.service) != primary.as_ref())
+            .map(|b| b.service.clone())
+            .collect()
+    };
+    let tiers = grouped.iter().map(|s| fallbacks(s)).collect::<Vec<_>>();
+    let depth = tiers.iter().map(Vec::len).min().unwrap_or(0);
+    let tier = (0..depth).find(|&t| {
+        grouped
+            .iter()
+            .zip(&tiers)
+            .all(|(s, fallbacks)| health.health(s, &fallbacks[t]).ready)
+    });
+    let target = match tier {
+        Some(t) => fallbacks(split).into_iter().nth(t),
+        None => None,
+    };
+    let target = match target {
+        Some(target) => target,
+        None => {
+            tracing::warn!(group = ?group(split), "no fallback tier is ready in every member of the failover group");
+            return Some(decision);
+        }
+    };
+
+    let backends = decision
+        .backends
+        .into_iter()
+        .map(|mut b| {
+            b.weight = if b.service == target { 1 } else { 0 };
+            b
+        })
+        .collect();
+    Some(Decision {
+        primary_active: false,
+        backends,
+        health: decision.health,
+        all_unavailable: false,
+    })
+}
+
+/// Returns the field manager used to patch traffic splits by a controller installed in `namespace`,
+/// unless otherwise configured
+pub fn default_field_manager(namespace: &str) -> String {
+    format!("{DEFAULT_FIELD_MANAGER}/{namespace}")
+}
+
+/// Returns the field managers that own the split's backends, other than `field_manager` and the
+/// legacy [`DEFAULT_FIELD_MANAGER`], that belong to failover controllers.
+pub fn competing_managers(split: &TrafficSplit, field_manager: &str) -> Vec<String> {
+    split
+        .managed_fields()
+        .iter()
+        .filter_map(|entry| {
+            let manager = entry.manager.as_deref()?;
+            let controller = manager
+                .strip_prefix(DEFAULT_FIELD_MANAGER)
+                .map_or(false, |suffix| suffix.starts_with('/'));
+            if manager == field_manager || !controller {
+                return None;
+            }
+            let fields = &entry.fields_v1.as_ref()?.0;
+            fields.get("f:spec")?.get("f:backends")?;
+            Some(manager.to_string())
+        })
+        .collect()
+}
+
 #[tracing::instrument(skip_all, fields(
-    namespace = %target.namespace.as_ref().unwrap(),
-    trafficsplit = %target.name
+    namespace = %update.target.namespace.as_ref().unwrap(),
+    trafficsplit = %update.target.name
 ))]
 async fn patch(
     client: kube::Client,
     params: &PatchParams,
     timeout: time::Duration,
-    FailoverUpdate {
-        target,
-        backends,
-        primary_active,
-    }: FailoverUpdate,
-) {
+    update: &FailoverUpdate,
+) -> bool {
+    let target = &update.target;
     let namespace = target.namespace.as_ref().expect("namespace must be set");
     let api = Api::<TrafficSplit>::namespaced(client.clone(), namespace);
     let name = &target.name;
     tracing::debug!("patching trafficsplit");
 
-    let patch = mk_patch(name, &backends);
+    let patch = mk_patch(name, &update.backends);
     tracing::trace!(?patch);
 
-    match time::timeout(timeout, api.patch(name, params, &Patch::Merge(patch))).await {
-        Ok(Ok(_)) => tracing::trace!("patched trafficsplit"),
-        Err(_) => tracing::warn!(?timeout, "failed to patch traffic split"),
+    let patched = match time::timeout(timeout, api.patch(name, params, &Patch::Merge(patch))).await
+    {
+        Ok(Ok(_)) => {
+            tracing::trace!("patched trafficsplit");
+            true
+        }
+        Err(_) => {
+            tracing::warn!(?timeout, "failed to patch traffic split");
+            false
+        }
         Ok(Err(error)) => {
             // TODO requeue?
             tracing::warn!(%error, "failed to patch traffic split");
+            false
         }
-    }
+    };
 
-    record_event(client, target.clone(), primary_active).await;
+    if let Some(note) = update.competing_managers_note() {
+        record_warning(client.clone(), target.clone(), COMPETING_CONTROLLER, note).await;
+    }
+    match update.all_unavailable_note() {
+        Some(note) => record_warning(client, target.clone(), ALL_BACKENDS_UNAVAILABLE, note).await,
+        None => record_event(client, target.clone(), update.event_note()).await,
+    }
+    patched
 }
 
 fn mk_patch(name: &str, backends: &[Backend]) -> serde_json::Value {
@@ -214,16 +969,11 @@ fn mk_patch(name: &str, backends: &[Backend]) -> serde_json::Value {
     })
 }
 
-async fn record_event(client: kube::Client, target: ObjectRef<TrafficSplit>, primary_active: bool) {
+async fn record_event(client: kube::Client, target: ObjectRef<TrafficSplit>, description: String) {
     let event_reporter = events::Reporter {
         controller: CONTROLLER_NAME.to_string(),
         instance: None,
     };
-    let description = if primary_active {
-        format!("trafficsplit/{} switching traffic to primary", &target.name)
-    } else {
-        format!("trafficsplit/{} failing over to fallbacks", &target.name)
-    };
     let event_recorder = events::Recorder::new(client, event_reporter, target.into());
 
     if let Err(error) = event_recorder
@@ -239,3 +989,29 @@ async fn record_event(client: kube::Client, target: ObjectRef<TrafficSplit>, pri
         tracing::error!(%error, "failed to record event");
     }
 }
+
+async fn record_warning(
+    client: kube::Client,
+    target: ObjectRef<TrafficSplit>,
+    reason: &str,
+    description: String,
+) {
+    let event_reporter = events::Reporter {
+        controller: CONTROLLER_NAME.to_string(),
+        instance: None,
+    };
+    let event_recorder = events::Recorder::new(client, event_reporter, target.into());
+
+    if let Err(error) = event_recorder
+        .publish(events::Event {
+            type_: events::EventType::Warning,
+            reason: reason.to_string(),
+            note: Some(description),
+            action: FAILOVER.to_string(),
+            secondary: None,
+        })
+        .await
+    {
+        tracing::error!(%error, "failed to record event");
+    }
+}